@@ -79,7 +79,12 @@ export default function App() {
   };
 
   const loadHistory = async (id: string) => {
-    const h: RunHistoryResponse = await getHistory(id);
+    let h: RunHistoryResponse = await getHistory(id);
+    // Long runs span several pages; the chart only needs the latest one.
+    if (h.total > h.points.length) {
+      const limit = h.points.length;
+      h = await getHistory(id, undefined, { offset: h.total - limit, limit });
+    }
     setHistory(h.points || []);
   };
 
@@ -1,7 +1,13 @@
 import {
+  BestGenomeResponse,
+  EvaluateRequest,
+  EvaluateResponse,
+  ParetoResponse,
   RunAdvanceRequest,
   RunConfig,
+  RunEvent,
   RunHistoryResponse,
+  RunSnapshot,
   RunState,
 } from "./types";
 
@@ -64,7 +70,77 @@ export async function advanceRun(
   return handleResponse<RunState>(res);
 }
 
-export async function getHistory(run_id: string): Promise<RunHistoryResponse> {
-  const res = await fetch(`${BASE}/runs/${run_id}/history`);
+async function controlRun(
+  run_id: string,
+  action: "start" | "pause" | "resume" | "cancel",
+): Promise<RunState> {
+  const res = await fetch(`${BASE}/runs/${run_id}/${action}`, { method: "POST" });
+  return handleResponse<RunState>(res);
+}
+
+export const startRun = (run_id: string) => controlRun(run_id, "start");
+export const pauseRun = (run_id: string) => controlRun(run_id, "pause");
+export const resumeRun = (run_id: string) => controlRun(run_id, "resume");
+export const cancelRun = (run_id: string) => controlRun(run_id, "cancel");
+
+export async function getHistory(
+  run_id: string,
+  fields?: string[],
+  page?: { offset?: number; limit?: number },
+): Promise<RunHistoryResponse> {
+  const params = new URLSearchParams();
+  if (fields) params.set("fields", fields.join(","));
+  if (page?.offset !== undefined) params.set("offset", String(page.offset));
+  if (page?.limit !== undefined) params.set("limit", String(page.limit));
+  const query = params.toString() ? `?${params}` : "";
+  const res = await fetch(`${BASE}/runs/${run_id}/history${query}`);
   return handleResponse<RunHistoryResponse>(res);
 }
+
+export async function getPareto(run_id: string): Promise<ParetoResponse> {
+  const res = await fetch(`${BASE}/runs/${run_id}/pareto`);
+  return handleResponse<ParetoResponse>(res);
+}
+
+export async function getBestGenome(run_id: string): Promise<BestGenomeResponse> {
+  const res = await fetch(`${BASE}/runs/${run_id}/best`);
+  return handleResponse<BestGenomeResponse>(res);
+}
+
+export async function getSnapshot(run_id: string): Promise<RunSnapshot> {
+  const res = await fetch(`${BASE}/runs/${run_id}/snapshot`);
+  return handleResponse<RunSnapshot>(res);
+}
+
+export async function importRun(snapshot: RunSnapshot) {
+  const res = await fetch(`${BASE}/runs/import`, {
+    method: "POST",
+    headers: { "Content-Type": "application/json" },
+    body: JSON.stringify(snapshot),
+  });
+  return handleResponse<{ run_id: string }>(res);
+}
+
+export async function evaluateGenome(
+  req: EvaluateRequest,
+  trace = false,
+): Promise<EvaluateResponse> {
+  const res = await fetch(`${BASE}/evaluate${trace ? "?trace=true" : ""}`, {
+    method: "POST",
+    headers: { "Content-Type": "application/json" },
+    body: JSON.stringify(req),
+  });
+  return handleResponse<EvaluateResponse>(res);
+}
+
+/** Calls `onEvent` after every generation of the run; returns a function that unsubscribes. */
+export function subscribeRunEvents(
+  run_id: string,
+  onEvent: (event: RunEvent) => void,
+): () => void {
+  const source = new EventSource(`${BASE}/runs/${run_id}/events`);
+  source.addEventListener("generation", (e) => {
+    onEvent(JSON.parse((e as MessageEvent).data) as RunEvent);
+  });
+  return () => source.close();
+}
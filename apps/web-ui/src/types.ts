@@ -7,14 +7,61 @@ export type Genome = {
   instructions: Instruction[];
 };
 
+export type CrossoverKind = "one_point" | "two_point" | "uniform" | "homologous";
+
+export type SelectionConfig =
+  | { method: "tournament"; k?: number }
+  | { method: "roulette" }
+  | { method: "rank" }
+  | { method: "truncation"; fraction: number }
+  | { method: "sus" }
+  | { method: "lexicase" }
+  | { method: "epsilon_lexicase"; epsilon?: number | null };
+
+export type EvolutionMode = "single" | "nsga2";
+
+export type IslandConfig = {
+  count: number;
+  migration_interval: number;
+  migrants: number;
+  topology?: "ring" | "random";
+};
+
+export type StopCriteria = {
+  target_fitness?: number | null;
+  stagnation_generations?: number | null;
+  max_wall_time_secs?: number | null;
+  max_evaluations?: number | null;
+};
+
+export type StopReason =
+  | "generation_limit"
+  | "target_fitness"
+  | "stagnation"
+  | "max_wall_time"
+  | "max_evaluations";
+
 export type RunConfig = {
   seed: number;
   population: number;
   generations: number;
   mutation_rate: number;
   task: string;
+  evaluator?: "http" | "native";
+  allowed_ops?: string[] | null;
+  op_weights?: Record<string, number> | null;
+  crossover_rate?: number;
+  crossover?: CrossoverKind;
+  selection?: SelectionConfig;
+  mode?: EvolutionMode;
+  islands?: IslandConfig | null;
+  stop?: StopCriteria;
+  seed_genomes?: string[] | null;
+  replace_duplicates?: boolean;
 };
 
+export type RunStatus = "idle" | "running" | "paused" | "finished" | "failed";
+
 export type RunState = {
   run_id: string;
   generation: number;
@@ -25,19 +72,142 @@ export type RunState = {
   generations?: number;
   mutation_rate?: number;
   task?: string;
+  evaluator?: "http" | "native";
+  allowed_ops?: string[] | null;
+  op_weights?: Record<string, number> | null;
+  crossover_rate?: number;
+  crossover?: CrossoverKind;
+  selection?: SelectionConfig;
+  mode?: EvolutionMode;
+  islands?: IslandConfig | null;
+  island_best_fitness?: number[] | null;
+  stop?: StopCriteria;
+  status?: RunStatus;
+  error?: string | null;
+  stop_reason?: StopReason | null;
+  evaluations?: number;
+  elapsed_secs?: number;
+  cache_hits?: number;
+  cache_hit_rate?: number;
+  replace_duplicates?: boolean;
+  unique_count?: number;
+  duplicates_replaced?: number;
+};
+
+export type RunEvent = {
+  run_id: string;
+  generation: number;
+  best_fitness: number;
+  mean_fitness: number;
+  best_genome: Genome;
+  status: RunStatus;
 };
 
 export type RunHistoryPoint = {
   generation: number;
   best_fitness: number;
+  mean_fitness?: number;
+  median_fitness?: number;
+  std_fitness?: number;
+  worst_fitness?: number;
+  invalid_fraction?: number;
+  mean_length?: number;
+  unique_count?: number;
+  cache_hit_rate?: number;
+  duplicates_replaced?: number;
 };
 
 export type RunHistoryResponse = {
   run_id: string;
   task: string;
+  total: number;
   points: RunHistoryPoint[];
 };
 
 export type RunAdvanceRequest = {
   steps: number;
 };
+
+export type ParetoPoint = {
+  genome: Genome;
+  fitness: number;
+  error: number;
+  length: number;
+  steps: number;
+};
+
+export type ParetoResponse = {
+  run_id: string;
+  generation: number;
+  front: ParetoPoint[];
+};
+
+export type BestGenomeResponse = {
+  run_id: string;
+  generation: number;
+  best_fitness: number;
+  genome: Genome;
+  effective: boolean[];
+  simplified: Genome;
+};
+
+export type RngState = {
+  seed: number[];
+  stream: number;
+  word_pos: number;
+};
+
+export type RunSnapshot = {
+  version: number;
+  run_id?: string;
+  config: RunConfig;
+  generation: number;
+  population: Genome[];
+  fitness: number[];
+  case_errors: (number | null)[][];
+  steps: number[];
+  best_fitness: number;
+  best_genome: Genome;
+  history: RunHistoryPoint[];
+  rng: RngState;
+  island_rngs: RngState[];
+  status?: RunStatus;
+  error?: string | null;
+  stop_reason?: StopReason | null;
+  evaluations?: number;
+  elapsed_secs?: number;
+  cache_hits?: number;
+  duplicates_replaced?: number;
+};
+
+export type VmConfig = {
+  max_steps?: number;
+};
+
+export type EvaluateRequest = {
+  genome: Genome;
+  inputs: number[];
+  vm?: VmConfig;
+};
+
+export type TraceStep = {
+  pc: number;
+  op: string;
+  arg: number | null;
+  stack: (number | null)[];
+  registers: (number | null)[];
+};
+
+export type VmTrace = {
+  steps: TraceStep[];
+  failed_at: number | null;
+};
+
+export type EvaluateResult = { input: number; trace?: VmTrace } & (
+  | { status: "ok"; output: number; steps: number }
+  | { status: "invalid"; reason: string }
+);
+
+export type EvaluateResponse = {
+  results: EvaluateResult[];
+};
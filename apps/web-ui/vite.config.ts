@@ -11,6 +11,7 @@ export default defineConfig({
         target: "http://127.0.0.1:8080",
         changeOrigin: true,
         secure: false,
+        ws: true,
         rewrite: (path) => path.replace(/^\/api/, ""),
       },
     },
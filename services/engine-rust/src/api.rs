@@ -1,54 +1,139 @@
+use std::convert::Infallible;
 use std::env;
+use std::sync::Arc;
 
 use axum::{
-    extract::{Path, State},
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Path, Query, State,
+    },
+    http::{header, HeaderMap},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
     routing::{get, post},
     Json, Router,
 };
 use serde_json::json;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio_stream::{wrappers::BroadcastStream, Stream, StreamExt};
 
 use crate::error::EngineError;
+use crate::models::evaluator::Evaluators;
 use crate::models::evolve::{
-    advance_run, create_run, get_history, get_run_state, new_store, step_run, RunStore,
+    advance_run, cancel_run, create_run, get_history, get_pareto_front, get_run_state,
+    get_snapshot, import_run, new_store, open_store, pause_run, resume_background_runs,
+    resume_run, start_run, step_run, subscribe, RunStore,
 };
+use crate::models::genome;
+use crate::models::stats;
+use crate::models::storage::RunDb;
+use crate::models::vm::{run_genome, trace_genome};
 use crate::models::{
-    RunAdvanceRequest, RunConfig, RunHistoryPoint, RunHistoryResponse, RunState,
+    BestGenomeResponse, EvaluateQuery, EvaluateRequest, EvaluateResponse, EvaluateResult,
+    EvolutionMode, ParetoResponse, RunAdvanceRequest, RunConfig, RunEvent, RunHistoryQuery,
+    RunHistoryResponse, RunSnapshot, RunState, SelectionConfig,
 };
 
+/// Most history points one `GET /runs/{id}/history` request returns.
+const HISTORY_PAGE_MAX: usize = 1000;
+
 #[derive(Clone)]
 struct AppState {
     runs: RunStore,
-    fitness_url: String,
+    evaluators: Evaluators,
 }
 
-pub fn router() -> Router {
+/// The app router, plus its run store so pending writes can be flushed on shutdown.
+pub async fn router() -> Result<(Router, RunStore), EngineError> {
     let fitness_url =
         env::var("FITNESS_URL").unwrap_or_else(|_| "http://127.0.0.1:8090".to_string());
+    // Runs stay in memory only unless ENGINE_DB_PATH names a database file.
+    let db_path = env::var("ENGINE_DB_PATH").unwrap_or_default();
+    let runs = if db_path.is_empty() {
+        new_store()
+    } else {
+        tracing::info!("persisting runs to {}", db_path);
+        open_store(Arc::new(RunDb::open(&db_path)?))?
+    };
+    // Threads for the native evaluator; 0 (the default) means one per CPU core.
+    let eval_threads = match env::var("ENGINE_EVAL_THREADS") {
+        Ok(value) => value.parse().map_err(|_| {
+            EngineError::InternalError(format!("invalid ENGINE_EVAL_THREADS {value}"))
+        })?,
+        Err(_) => 0,
+    };
+    let evaluators = Evaluators::new(&fitness_url, eval_threads)?;
+    resume_background_runs(&runs, &evaluators).await;
     let state = AppState {
-        runs: new_store(),
-        fitness_url,
+        runs: runs.clone(),
+        evaluators,
     };
 
-    Router::new()
+    let router = Router::new()
         .route("/health", get(health))
+        .route("/evaluate", post(evaluate_handler))
         .route("/runs", post(create_run_handler))
         .route("/runs/:run_id", get(get_run))
         .route("/runs/:run_id/step", post(step_run_handler))
         .route("/runs/:run_id/history", get(get_history_handler))
         .route("/runs/:run_id/advance", post(advance_run_handler))
-        .with_state(state)
+        .route("/runs/:run_id/start", post(start_run_handler))
+        .route("/runs/:run_id/pause", post(pause_run_handler))
+        .route("/runs/:run_id/resume", post(resume_run_handler))
+        .route("/runs/:run_id/cancel", post(cancel_run_handler))
+        .route("/runs/:run_id/pareto", get(get_pareto_handler))
+        .route("/runs/:run_id/best", get(get_best_handler))
+        .route("/runs/:run_id/events", get(events_handler))
+        .route("/runs/:run_id/ws", get(ws_handler))
+        .route("/runs/:run_id/snapshot", get(get_snapshot_handler))
+        .route("/runs/import", post(import_run_handler))
+        .with_state(state);
+    Ok((router, runs))
 }
 
 async fn health() -> Json<serde_json::Value> {
     Json(json!({ "status": "ok" }))
 }
 
+/// Runs a genome on each input with the VM, for debugging individual programs.
+/// `?trace=true` adds the per-step machine state to every result.
+async fn evaluate_handler(
+    Query(query): Query<EvaluateQuery>,
+    Json(req): Json<EvaluateRequest>,
+) -> Result<Json<EvaluateResponse>, EngineError> {
+    validate_evaluate(&req, query.trace)?;
+    // Up to 1000 inputs of 100k steps each: run them off the async executor.
+    let results = tokio::task::spawn_blocking(move || {
+        req.inputs
+            .iter()
+            .map(|&input| {
+                let (outcome, trace) = if query.trace {
+                    let (outcome, trace) = trace_genome(&req.genome, input, req.vm);
+                    (outcome, Some(trace))
+                } else {
+                    (run_genome(&req.genome, input, req.vm), None)
+                };
+                EvaluateResult {
+                    input,
+                    outcome,
+                    trace,
+                }
+            })
+            .collect()
+    })
+    .await
+    .map_err(|e| EngineError::InternalError(format!("evaluation failed: {e}")))?;
+    Ok(Json(EvaluateResponse { results }))
+}
+
 async fn create_run_handler(
     State(state): State<AppState>,
     Json(cfg): Json<RunConfig>,
 ) -> Result<Json<serde_json::Value>, EngineError> {
     validate_run_config(&cfg)?;
-    let run_id = create_run(cfg, &state.runs, &state.fitness_url).await?;
+    let run_id = create_run(cfg, &state.runs, &state.evaluators).await?;
     tracing::info!("created run {}", run_id);
     Ok(Json(json!({ "run_id": run_id })))
 }
@@ -56,9 +141,10 @@ async fn create_run_handler(
 async fn get_run(
     State(state): State<AppState>,
     Path(run_id): Path<String>,
-) -> Result<Json<RunState>, EngineError> {
+    headers: HeaderMap,
+) -> Result<Response, EngineError> {
     if let Some(run) = get_run_state(&state.runs, &run_id).await {
-        Ok(Json(run))
+        Ok(run_response(&headers, run))
     } else {
         Err(EngineError::NotFound("run not found".to_string()))
     }
@@ -67,31 +153,74 @@ async fn get_run(
 async fn step_run_handler(
     State(state): State<AppState>,
     Path(run_id): Path<String>,
-) -> Result<Json<RunState>, EngineError> {
-    let updated = step_run(&state.runs, &run_id, &state.fitness_url).await?;
+    headers: HeaderMap,
+) -> Result<Response, EngineError> {
+    let updated = step_run(&state.runs, &run_id, &state.evaluators).await?;
     tracing::info!("stepped run {} to generation {}", run_id, updated.generation);
-    Ok(Json(updated))
+    Ok(run_response(&headers, updated))
+}
+
+async fn start_run_handler(
+    State(state): State<AppState>,
+    Path(run_id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, EngineError> {
+    let run = start_run(&state.runs, &run_id, &state.evaluators).await?;
+    Ok(run_response(&headers, run))
+}
+
+async fn pause_run_handler(
+    State(state): State<AppState>,
+    Path(run_id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, EngineError> {
+    let run = pause_run(&state.runs, &run_id).await?;
+    Ok(run_response(&headers, run))
+}
+
+async fn resume_run_handler(
+    State(state): State<AppState>,
+    Path(run_id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, EngineError> {
+    let run = resume_run(&state.runs, &run_id, &state.evaluators).await?;
+    Ok(run_response(&headers, run))
+}
+
+async fn cancel_run_handler(
+    State(state): State<AppState>,
+    Path(run_id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, EngineError> {
+    let run = cancel_run(&state.runs, &run_id).await?;
+    Ok(run_response(&headers, run))
 }
 
 async fn get_history_handler(
     State(state): State<AppState>,
     Path(run_id): Path<String>,
+    Query(query): Query<RunHistoryQuery>,
 ) -> Result<Json<RunHistoryResponse>, EngineError> {
-    let maybe_points = get_history(&state.runs, &run_id).await;
-    if let Some(points) = maybe_points {
+    let fields = stats::parse_fields(query.fields.as_deref()).map_err(EngineError::BadRequest)?;
+    let limit = query.limit.unwrap_or(HISTORY_PAGE_MAX);
+    if !(1..=HISTORY_PAGE_MAX).contains(&limit) {
+        return Err(EngineError::BadRequest(format!(
+            "limit must be between 1 and {HISTORY_PAGE_MAX}"
+        )));
+    }
+    let maybe_points = get_history(&state.runs, &run_id, query.offset, limit).await;
+    if let Some((total, points)) = maybe_points {
         let run_state = get_run_state(&state.runs, &run_id)
             .await
             .ok_or_else(|| EngineError::NotFound("run not found".to_string()))?;
         let points = points
-            .into_iter()
-            .map(|(generation, best_fitness)| RunHistoryPoint {
-                generation,
-                best_fitness,
-            })
+            .iter()
+            .map(|p| stats::select_fields(p, &fields))
             .collect();
         Ok(Json(RunHistoryResponse {
             run_id,
             task: run_state.task.clone(),
+            total,
             points,
         }))
     } else {
@@ -99,13 +228,117 @@ async fn get_history_handler(
     }
 }
 
+async fn get_pareto_handler(
+    State(state): State<AppState>,
+    Path(run_id): Path<String>,
+) -> Result<Json<ParetoResponse>, EngineError> {
+    let (generation, front) = get_pareto_front(&state.runs, &run_id)
+        .await
+        .ok_or_else(|| EngineError::NotFound("run not found".to_string()))?;
+    Ok(Json(ParetoResponse {
+        run_id,
+        generation,
+        front,
+    }))
+}
+
+/// The best genome with its introns marked and removed.
+async fn get_best_handler(
+    State(state): State<AppState>,
+    Path(run_id): Path<String>,
+) -> Result<Json<BestGenomeResponse>, EngineError> {
+    let run = get_run_state(&state.runs, &run_id)
+        .await
+        .ok_or_else(|| EngineError::NotFound("run not found".to_string()))?;
+    Ok(Json(BestGenomeResponse {
+        effective: genome::effective_instructions(&run.best_genome),
+        simplified: genome::simplify(&run.best_genome),
+        run_id,
+        generation: run.generation,
+        best_fitness: run.best_fitness,
+        genome: run.best_genome,
+    }))
+}
+
+/// Server-Sent Events stream of [`RunEvent`]s, one `generation` event per message.
+async fn events_handler(
+    State(state): State<AppState>,
+    Path(run_id): Path<String>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, EngineError> {
+    let events = subscribe(&state.runs, &run_id)
+        .await
+        .ok_or_else(|| EngineError::NotFound("run not found".to_string()))?;
+    // Lagging subscribers lose the skipped events rather than the connection.
+    let stream = BroadcastStream::new(events).filter_map(|event| {
+        let event = event.ok()?;
+        Event::default().event("generation").json_data(event).ok().map(Ok)
+    });
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+/// WebSocket equivalent of `/events`: each [`RunEvent`] is sent as a JSON text frame.
+async fn ws_handler(
+    State(state): State<AppState>,
+    Path(run_id): Path<String>,
+    ws: WebSocketUpgrade,
+) -> Result<Response, EngineError> {
+    let events = subscribe(&state.runs, &run_id)
+        .await
+        .ok_or_else(|| EngineError::NotFound("run not found".to_string()))?;
+    Ok(ws.on_upgrade(move |socket| forward_events(socket, events)))
+}
+
+async fn forward_events(mut socket: WebSocket, mut events: broadcast::Receiver<RunEvent>) {
+    loop {
+        let event = tokio::select! {
+            event = events.recv() => event,
+            incoming = socket.recv() => match incoming {
+                Some(Ok(_)) => continue,
+                _ => return,
+            },
+        };
+        let text = match event {
+            Ok(event) => match serde_json::to_string(&event) {
+                Ok(text) => text,
+                Err(_) => continue,
+            },
+            Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => return,
+        };
+        if socket.send(Message::Text(text)).await.is_err() {
+            return;
+        }
+    }
+}
+
+async fn get_snapshot_handler(
+    State(state): State<AppState>,
+    Path(run_id): Path<String>,
+) -> Result<Json<RunSnapshot>, EngineError> {
+    get_snapshot(&state.runs, &run_id)
+        .await
+        .map(Json)
+        .ok_or_else(|| EngineError::NotFound("run not found".to_string()))
+}
+
+async fn import_run_handler(
+    State(state): State<AppState>,
+    Json(snapshot): Json<RunSnapshot>,
+) -> Result<Json<serde_json::Value>, EngineError> {
+    validate_run_config(&snapshot.config)?;
+    let run_id = import_run(snapshot, &state.runs).await?;
+    tracing::info!("imported run {}", run_id);
+    Ok(Json(json!({ "run_id": run_id })))
+}
+
 async fn advance_run_handler(
     State(state): State<AppState>,
     Path(run_id): Path<String>,
+    headers: HeaderMap,
     Json(req): Json<RunAdvanceRequest>,
-) -> Result<Json<RunState>, EngineError> {
+) -> Result<Response, EngineError> {
     validate_advance(&req)?;
-    let updated = advance_run(&state.runs, &run_id, req.steps, &state.fitness_url).await?;
+    let updated = advance_run(&state.runs, &run_id, req.steps, &state.evaluators).await?;
     tracing::info!(
         "advance run {} steps={} final_gen={} best={}",
         run_id,
@@ -113,7 +346,36 @@ async fn advance_run_handler(
         updated.generation,
         updated.best_fitness
     );
-    Ok(Json(updated))
+    Ok(run_response(&headers, updated))
+}
+
+/// Answers with the best genome in assembly syntax when the client prefers `text/plain`,
+/// otherwise with the JSON [`RunState`]. The header line is a comment, so the body can be
+/// pasted straight back into `seed_genomes`.
+fn run_response(headers: &HeaderMap, run: RunState) -> Response {
+    if !prefers_text(headers) {
+        return Json(run).into_response();
+    }
+    format!(
+        "; run {} generation {} best_fitness {}\n{}",
+        run.run_id,
+        run.generation,
+        run.best_fitness,
+        genome::to_assembly(&run.best_genome)
+    )
+    .into_response()
+}
+
+/// True when `text/plain` is listed in `Accept` ahead of any JSON type.
+fn prefers_text(headers: &HeaderMap) -> bool {
+    let Some(accept) = headers.get(header::ACCEPT).and_then(|v| v.to_str().ok()) else {
+        return false;
+    };
+    accept
+        .split(',')
+        .map(|part| part.split(';').next().unwrap_or("").trim())
+        .find(|media| *media == "text/plain" || *media == "application/json")
+        == Some("text/plain")
 }
 
 fn validate_run_config(cfg: &RunConfig) -> Result<(), EngineError> {
@@ -126,12 +388,104 @@ fn validate_run_config(cfg: &RunConfig) -> Result<(), EngineError> {
     if !(0.0..=1.0).contains(&cfg.mutation_rate) {
         return Err(EngineError::BadRequest("mutation_rate must be between 0 and 1".to_string()));
     }
+    if !(0.0..=1.0).contains(&cfg.crossover_rate) {
+        return Err(EngineError::BadRequest("crossover_rate must be between 0 and 1".to_string()));
+    }
+    match cfg.selection {
+        SelectionConfig::Tournament { k } if !(1..=100).contains(&k) => {
+            return Err(EngineError::BadRequest(
+                "tournament k must be between 1 and 100".to_string(),
+            ));
+        }
+        SelectionConfig::Truncation { fraction } if !(fraction > 0.0 && fraction <= 1.0) => {
+            return Err(EngineError::BadRequest(
+                "truncation fraction must be in (0, 1]".to_string(),
+            ));
+        }
+        SelectionConfig::EpsilonLexicase {
+            epsilon: Some(epsilon),
+        } if !(epsilon.is_finite() && epsilon >= 0.0) => {
+            return Err(EngineError::BadRequest(
+                "lexicase epsilon must be non-negative".to_string(),
+            ));
+        }
+        _ => {}
+    }
+    if cfg.mode == EvolutionMode::Nsga2 && cfg.selection != SelectionConfig::default() {
+        return Err(EngineError::BadRequest(
+            "selection is not used in nsga2 mode".to_string(),
+        ));
+    }
+    if let Some(islands) = &cfg.islands {
+        if cfg.mode != EvolutionMode::Single {
+            return Err(EngineError::BadRequest(
+                "islands are only supported in single mode".to_string(),
+            ));
+        }
+        if !(2..=64).contains(&islands.count) || islands.count as i64 > cfg.population {
+            return Err(EngineError::BadRequest(
+                "island count must be between 2 and 64 and at most population".to_string(),
+            ));
+        }
+        if islands.migration_interval == 0 {
+            return Err(EngineError::BadRequest(
+                "migration_interval must be at least 1".to_string(),
+            ));
+        }
+        let smallest_island = cfg.population as usize / islands.count;
+        if islands.migrants == 0 || islands.migrants >= smallest_island {
+            return Err(EngineError::BadRequest(
+                "migrants must be at least 1 and smaller than each island".to_string(),
+            ));
+        }
+    }
+    if cfg.stop.target_fitness.is_some_and(|t| !t.is_finite()) {
+        return Err(EngineError::BadRequest("target_fitness must be finite".to_string()));
+    }
+    if cfg.stop.stagnation_generations == Some(0) {
+        return Err(EngineError::BadRequest(
+            "stagnation_generations must be at least 1".to_string(),
+        ));
+    }
+    if cfg
+        .stop
+        .max_wall_time_secs
+        .is_some_and(|t| !t.is_finite() || t <= 0.0)
+    {
+        return Err(EngineError::BadRequest(
+            "max_wall_time_secs must be positive".to_string(),
+        ));
+    }
+    if cfg.stop.max_evaluations == Some(0) {
+        return Err(EngineError::BadRequest("max_evaluations must be at least 1".to_string()));
+    }
     if cfg.task.trim().is_empty() {
         return Err(EngineError::BadRequest("task must be non-empty".to_string()));
     }
     Ok(())
 }
 
+fn validate_evaluate(req: &EvaluateRequest, trace: bool) -> Result<(), EngineError> {
+    genome::check_length(&req.genome).map_err(EngineError::BadRequest)?;
+    if !(1..=1000).contains(&req.inputs.len()) {
+        return Err(EngineError::BadRequest(
+            "inputs must have between 1 and 1000 values".to_string(),
+        ));
+    }
+    if !(1..=100_000).contains(&req.vm.max_steps) {
+        return Err(EngineError::BadRequest(
+            "max_steps must be between 1 and 100000".to_string(),
+        ));
+    }
+    // Each step copies the stack, so traces grow quadratically with max_steps.
+    if trace && (req.inputs.len() > 10 || req.vm.max_steps > 1000) {
+        return Err(EngineError::BadRequest(
+            "trace supports at most 10 inputs and max_steps 1000".to_string(),
+        ));
+    }
+    Ok(())
+}
+
 fn validate_advance(req: &RunAdvanceRequest) -> Result<(), EngineError> {
     if !(1..=10_000).contains(&req.steps) {
         return Err(EngineError::BadRequest(
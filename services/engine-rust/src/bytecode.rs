@@ -0,0 +1,203 @@
+use crate::models::genome::{parse_jump_offset, parse_register_index};
+use crate::models::{Genome, Instruction};
+
+/// One decoded instruction. Operands are validated at decode time, so the VM never
+/// looks at op names or float args while it runs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Push(f64),
+    Load(usize),
+    Store(usize),
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Dup,
+    Swap,
+    Pop,
+    Halt,
+    Nop,
+    Jmp(isize),
+    Jz(isize),
+    Jnz(isize),
+    Lt,
+    Gt,
+    Eq,
+    Sin,
+    Cos,
+    Exp,
+    Log,
+    Sqrt,
+    Abs,
+    Neg,
+    /// An instruction that failed to decode. Genomes may carry these harmlessly, so the
+    /// program only becomes invalid if one is executed.
+    Fault(Fault),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    MissingPushArg,
+    InvalidRegister,
+    InvalidJump,
+    UnknownOpcode,
+}
+
+impl Fault {
+    pub fn reason(self) -> &'static str {
+        match self {
+            Fault::MissingPushArg => "PUSH missing arg",
+            Fault::InvalidRegister => "invalid register index",
+            Fault::InvalidJump => "invalid jump offset",
+            Fault::UnknownOpcode => "unknown opcode",
+        }
+    }
+}
+
+/// A genome decoded for execution; the JSON `Instruction` form stays the wire format.
+/// Instructions are decoded at most once, and a lazy program only decodes them as execution
+/// first reaches them, so genomes that fail within a few steps never pay for the rest.
+#[derive(Debug, Clone)]
+pub struct Program<'a> {
+    source: &'a [Instruction],
+    ops: Vec<Op>,
+}
+
+impl<'a> Program<'a> {
+    /// Decodes every instruction up front.
+    pub fn decode(genome: &'a Genome) -> Self {
+        let mut program = Self::lazy(genome);
+        program.decode_through(program.len());
+        program
+    }
+
+    /// Decodes nothing until the VM runs the program.
+    pub fn lazy(genome: &'a Genome) -> Self {
+        Self {
+            source: &genome.instructions,
+            ops: Vec::with_capacity(genome.instructions.len()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.source.len()
+    }
+
+    /// The instructions decoded so far, which is all of them after [`Program::decode`].
+    pub fn ops(&self) -> &[Op] {
+        &self.ops
+    }
+
+    /// The op at `pc`, decoding up to it first if needed. `pc` must be below [`Program::len`].
+    #[inline(always)]
+    pub fn op(&mut self, pc: usize) -> Op {
+        if pc >= self.ops.len() {
+            self.decode_through(pc + 1);
+        }
+        self.ops[pc]
+    }
+
+    fn decode_through(&mut self, end: usize) {
+        let start = self.ops.len();
+        self.ops.extend(
+            self.source[start..end]
+                .iter()
+                .map(|instr| decode_op(&instr.op, instr.arg)),
+        );
+    }
+}
+
+fn decode_op(op: &str, arg: Option<f64>) -> Op {
+    let register = |op: fn(usize) -> Op| {
+        parse_register_index(arg).map_or(Op::Fault(Fault::InvalidRegister), op)
+    };
+    let jump =
+        |op: fn(isize) -> Op| parse_jump_offset(arg).map_or(Op::Fault(Fault::InvalidJump), op);
+    match op {
+        "PUSH" => arg.map_or(Op::Fault(Fault::MissingPushArg), Op::Push),
+        "LOAD" => register(Op::Load),
+        "STORE" => register(Op::Store),
+        "ADD" => Op::Add,
+        "SUB" => Op::Sub,
+        "MUL" => Op::Mul,
+        "DIV" => Op::Div,
+        "DUP" => Op::Dup,
+        "SWAP" => Op::Swap,
+        "POP" => Op::Pop,
+        "HALT" => Op::Halt,
+        "NOP" => Op::Nop,
+        "JMP" => jump(Op::Jmp),
+        "JZ" => jump(Op::Jz),
+        "JNZ" => jump(Op::Jnz),
+        "LT" => Op::Lt,
+        "GT" => Op::Gt,
+        "EQ" => Op::Eq,
+        "SIN" => Op::Sin,
+        "COS" => Op::Cos,
+        "EXP" => Op::Exp,
+        "LOG" => Op::Log,
+        "SQRT" => Op::Sqrt,
+        "ABS" => Op::Abs,
+        "NEG" => Op::Neg,
+        _ => Op::Fault(Fault::UnknownOpcode),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_operands_and_defers_bad_ones() {
+        let genome = Genome {
+            instructions: [
+                ("PUSH", Some(1.5)),
+                ("LOAD", Some(3.0)),
+                ("STORE", Some(4.0)),
+                ("JNZ", Some(-2.0)),
+                ("JMP", Some(0.5)),
+                ("PUSH", None),
+                ("ADD", Some(9.0)),
+                ("FROB", None),
+            ]
+            .into_iter()
+            .map(|(op, arg)| Instruction {
+                op: op.to_string(),
+                arg,
+            })
+            .collect(),
+        };
+        assert_eq!(
+            Program::decode(&genome).ops(),
+            &[
+                Op::Push(1.5),
+                Op::Load(3),
+                Op::Fault(Fault::InvalidRegister),
+                Op::Jnz(-2),
+                Op::Fault(Fault::InvalidJump),
+                Op::Fault(Fault::MissingPushArg),
+                Op::Add,
+                Op::Fault(Fault::UnknownOpcode),
+            ]
+        );
+    }
+
+    #[test]
+    fn lazy_programs_decode_only_what_runs() {
+        let genome = Genome {
+            instructions: ["LOAD", "NEG", "FROB", "ADD"]
+                .into_iter()
+                .map(|op| Instruction {
+                    op: op.to_string(),
+                    arg: Some(0.0),
+                })
+                .collect(),
+        };
+        let mut program = Program::lazy(&genome);
+        assert!(program.ops().is_empty());
+        assert_eq!(program.op(1), Op::Neg);
+        assert_eq!(program.ops(), &[Op::Load(0), Op::Neg]);
+        assert_eq!(program.op(3), Op::Add);
+        assert_eq!(program.ops(), Program::decode(&genome).ops());
+    }
+}
@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::num::NonZeroUsize;
+
+use lru::LruCache;
+
+use crate::error::EngineError;
+use crate::models::fitness::Scores;
+use crate::models::Genome;
+
+/// Cache entries kept per individual; four generations' worth covers elites and
+/// unmutated clones that come back a few generations later.
+const GENERATIONS_CACHED: usize = 4;
+
+/// FNV-1a, so keys do not depend on the process or the std hasher's random seed.
+pub struct StableHasher(u64);
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for StableHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 ^= u64::from(b);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Cache key of `genome` scored on `task`, from the genome's canonical `Hash`.
+pub fn genome_key(task: &str, genome: &Genome) -> u64 {
+    let mut hasher = StableHasher::default();
+    task.hash(&mut hasher);
+    genome.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[derive(Debug, Clone)]
+struct CachedScore {
+    /// Checked on every hit, so two genomes whose keys collide never share a score.
+    genome: Genome,
+    fitness: f64,
+    case_errors: Vec<f64>,
+    steps: f64,
+}
+
+/// Least-recently-used scores of one run, keyed by [`genome_key`].
+pub struct FitnessCache {
+    entries: LruCache<u64, CachedScore>,
+}
+
+/// A population split into scores the cache already had and distinct genomes it did not.
+pub struct Pending {
+    /// Index into `distinct` for every individual, in population order.
+    slots: Vec<usize>,
+    /// One entry per distinct genome; `None` for those in `misses`, in the same order.
+    distinct: Vec<Option<CachedScore>>,
+    miss_keys: Vec<u64>,
+    /// Genomes to send to the evaluator, each at most once, in population order.
+    pub misses: Vec<Genome>,
+}
+
+impl Pending {
+    /// Individuals that do not need the evaluator, duplicates of a miss included.
+    pub fn hits(&self) -> usize {
+        self.slots.len() - self.misses.len()
+    }
+
+    /// Scores for the whole population, in order, given the evaluator's `scores` for
+    /// `misses`. Nothing is cached; [`FitnessCache::complete`] does that as well.
+    pub fn scores(&self, scores: &Scores) -> Result<Scores, EngineError> {
+        if scores.fitness.len() != self.misses.len() {
+            return Err(EngineError::InternalError(
+                "fitness response length mismatch".to_string(),
+            ));
+        }
+        let mut next_miss = 0;
+        let distinct: Vec<(f64, &[f64], f64)> = self
+            .distinct
+            .iter()
+            .map(|entry| match entry {
+                Some(score) => (score.fitness, score.case_errors.as_slice(), score.steps),
+                None => {
+                    let i = next_miss;
+                    next_miss += 1;
+                    (
+                        scores.fitness[i],
+                        scores.case_errors.get(i).map_or(&[][..], Vec::as_slice),
+                        scores.steps.get(i).copied().unwrap_or(0.0),
+                    )
+                }
+            })
+            .collect();
+        let mut all = Scores::default();
+        for &slot in &self.slots {
+            let (fitness, case_errors, steps) = distinct[slot];
+            all.fitness.push(fitness);
+            all.case_errors.push(case_errors.to_vec());
+            all.steps.push(steps);
+        }
+        Ok(all)
+    }
+}
+
+impl FitnessCache {
+    /// Sized to hold a few generations of a population of `size`.
+    pub fn for_population(size: usize) -> Self {
+        let capacity =
+            NonZeroUsize::new(size.max(1) * GENERATIONS_CACHED).unwrap_or(NonZeroUsize::MIN);
+        Self {
+            entries: LruCache::new(capacity),
+        }
+    }
+
+    /// Adds scores that are already known, such as those of a restored run's population.
+    pub fn insert_scored(
+        &mut self,
+        task: &str,
+        genomes: &[Genome],
+        fitness: &[f64],
+        case_errors: &[Vec<f64>],
+        steps: &[f64],
+    ) {
+        for (i, (genome, &fitness)) in genomes.iter().zip(fitness).enumerate() {
+            let score = CachedScore {
+                genome: genome.clone(),
+                fitness,
+                case_errors: case_errors.get(i).cloned().unwrap_or_default(),
+                steps: steps.get(i).copied().unwrap_or(0.0),
+            };
+            self.entries.put(genome_key(task, genome), score);
+        }
+    }
+
+    pub fn lookup(&mut self, task: &str, genomes: &[Genome]) -> Pending {
+        let mut seen: HashMap<&Genome, usize> = HashMap::new();
+        let mut slots = Vec::with_capacity(genomes.len());
+        let mut distinct = Vec::new();
+        let mut miss_keys = Vec::new();
+        let mut misses = Vec::new();
+        for genome in genomes {
+            if let Some(&slot) = seen.get(genome) {
+                slots.push(slot);
+                continue;
+            }
+            let key = genome_key(task, genome);
+            let cached = self
+                .entries
+                .get(&key)
+                .filter(|score| score.genome == *genome)
+                .cloned();
+            if cached.is_none() {
+                miss_keys.push(key);
+                misses.push(genome.clone());
+            }
+            seen.insert(genome, distinct.len());
+            slots.push(distinct.len());
+            distinct.push(cached);
+        }
+        Pending {
+            slots,
+            distinct,
+            miss_keys,
+            misses,
+        }
+    }
+
+    /// Caches the evaluator's `scores` for `pending.misses` and returns scores for the
+    /// whole population, in order.
+    pub fn complete(&mut self, pending: Pending, scores: Scores) -> Result<Scores, EngineError> {
+        let all = pending.scores(&scores)?;
+        let fresh = pending
+            .misses
+            .into_iter()
+            .zip(pending.miss_keys)
+            .zip(scores.fitness)
+            .zip(scores.case_errors.into_iter().chain(std::iter::repeat_with(Vec::new)))
+            .zip(scores.steps.into_iter().chain(std::iter::repeat(0.0)));
+        for ((((genome, key), fitness), case_errors), steps) in fresh {
+            let score = CachedScore {
+                genome,
+                fitness,
+                case_errors,
+                steps,
+            };
+            self.entries.put(key, score);
+        }
+        Ok(all)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::fitness::score_genomes;
+    use crate::models::genome::{random_genome, OpSet};
+    use crate::models::rng::seeded_rng;
+
+    fn population() -> Vec<Genome> {
+        let ops = OpSet::from_config(None, None).unwrap();
+        let mut rng = seeded_rng(11);
+        let mut genomes: Vec<Genome> = (0..20).map(|_| random_genome(&mut rng, &ops)).collect();
+        genomes.push(genomes[3].clone());
+        genomes.push(genomes[0].clone());
+        genomes
+    }
+
+    #[test]
+    fn keys_depend_on_task_and_constant_bits() {
+        let genome = population().remove(0);
+        let mut pushed = genome.clone();
+        pushed.instructions.insert(
+            0,
+            crate::models::Instruction {
+                op: "PUSH".to_string(),
+                arg: Some(0.0),
+            },
+        );
+        let mut negative_zero = pushed.clone();
+        negative_zero.instructions[0].arg = Some(-0.0);
+        assert_eq!(
+            genome_key("poly2", &genome),
+            genome_key("poly2", &genome.clone())
+        );
+        assert_ne!(genome_key("poly2", &genome), genome_key("other", &genome));
+        assert_ne!(
+            genome_key("poly2", &pushed),
+            genome_key("poly2", &negative_zero)
+        );
+    }
+
+    #[test]
+    fn colliding_keys_do_not_share_scores() {
+        let genomes = population();
+        let (a, b) = (&genomes[0], &genomes[1]);
+        let mut cache = FitnessCache::for_population(genomes.len());
+        cache.insert_scored("poly2", &genomes[1..2], &[0.5], &[vec![1.0]], &[2.0]);
+        // Simulate a hash collision: b's score stored under a's key.
+        let stolen = cache.entries.pop(&genome_key("poly2", b)).unwrap();
+        cache.entries.put(genome_key("poly2", a), stolen);
+
+        let pending = cache.lookup("poly2", &[a.clone(), b.clone()]);
+        assert_eq!(pending.misses, vec![a.clone(), b.clone()]);
+        let scores = cache.complete(pending, score_genomes("poly2", &genomes[..2]));
+        assert_eq!(scores.unwrap().fitness, score_genomes("poly2", &genomes[..2]).fitness);
+    }
+
+    #[test]
+    fn cached_scores_match_direct_scoring() {
+        let genomes = population();
+        let direct = score_genomes("poly2", &genomes);
+        let mut cache = FitnessCache::for_population(genomes.len());
+
+        let pending = cache.lookup("poly2", &genomes);
+        assert_eq!(pending.hits(), 2);
+        let fresh = score_genomes("poly2", &pending.misses);
+        let first = cache.complete(pending, fresh).unwrap();
+
+        let pending = cache.lookup("poly2", &genomes);
+        assert_eq!(pending.hits(), genomes.len());
+        let second = cache.complete(pending, Scores::default()).unwrap();
+
+        for scores in [first, second] {
+            let bits = |v: &[f64]| v.iter().map(|f| f.to_bits()).collect::<Vec<_>>();
+            assert_eq!(bits(&scores.fitness), bits(&direct.fitness));
+            assert_eq!(bits(&scores.steps), bits(&direct.steps));
+            assert_eq!(scores.case_errors, direct.case_errors);
+        }
+    }
+}
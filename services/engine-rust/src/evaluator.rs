@@ -0,0 +1,190 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use rayon::{ThreadPool, ThreadPoolBuilder};
+
+use crate::error::EngineError;
+use crate::models::fitness::{self, Scores};
+use crate::models::{EvaluatorKind, Genome};
+
+pub type ScoreFuture<'a> = Pin<Box<dyn Future<Output = Result<Scores, EngineError>> + Send + 'a>>;
+
+pub trait FitnessEvaluator: Send + Sync {
+    fn score<'a>(&'a self, task: &'a str, genomes: &'a [Genome]) -> ScoreFuture<'a>;
+}
+
+pub type SharedEvaluator = Arc<dyn FitnessEvaluator>;
+
+#[derive(Clone)]
+pub struct Evaluators {
+    pub http: SharedEvaluator,
+    pub native: SharedEvaluator,
+}
+
+impl Evaluators {
+    pub fn new(fitness_url: &str, native_threads: usize) -> Result<Self, EngineError> {
+        Ok(Self {
+            http: Arc::new(HttpEvaluator::new(fitness_url)),
+            native: Arc::new(NativeEvaluator::new(native_threads)?),
+        })
+    }
+
+    pub fn get(&self, kind: EvaluatorKind) -> &SharedEvaluator {
+        match kind {
+            EvaluatorKind::Http => &self.http,
+            EvaluatorKind::Native => &self.native,
+        }
+    }
+}
+
+pub struct HttpEvaluator {
+    client: reqwest::Client,
+    fitness_url: String,
+}
+
+impl HttpEvaluator {
+    pub fn new(fitness_url: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            fitness_url: fitness_url.trim_end_matches('/').to_string(),
+        }
+    }
+}
+
+impl FitnessEvaluator for HttpEvaluator {
+    fn score<'a>(&'a self, task: &'a str, genomes: &'a [Genome]) -> ScoreFuture<'a> {
+        Box::pin(async move {
+            #[derive(serde::Deserialize)]
+            struct ScoreResponse {
+                fitness: Vec<f64>,
+                // Older fitness services omit per-case errors; lexicase then has no cases to filter on.
+                #[serde(default)]
+                errors: Vec<Vec<Option<f64>>>,
+                #[serde(default)]
+                steps: Vec<f64>,
+            }
+
+            let url = format!("{}/score", self.fitness_url);
+            let resp = self
+                .client
+                .post(url)
+                .json(&serde_json::json!({ "task": task, "genomes": genomes }))
+                .send()
+                .await
+                .map_err(|e| EngineError::InternalError(format!("fitness request failed: {e}")))?;
+
+            let resp = resp
+                .error_for_status()
+                .map_err(|e| EngineError::InternalError(format!("fitness status error: {e}")))?;
+
+            let body: ScoreResponse = resp
+                .json()
+                .await
+                .map_err(|e| EngineError::InternalError(format!("fitness decode failed: {e}")))?;
+
+            if body.fitness.len() != genomes.len() {
+                return Err(EngineError::InternalError(
+                    "fitness response length mismatch".to_string(),
+                ));
+            }
+            let case_errors = if body.errors.is_empty() {
+                vec![Vec::new(); genomes.len()]
+            } else if body.errors.len() == genomes.len() {
+                // Invalid genomes come back as nulls since JSON has no infinity.
+                body.errors
+                    .into_iter()
+                    .map(|row| row.into_iter().map(|e| e.unwrap_or(f64::INFINITY)).collect())
+                    .collect()
+            } else {
+                return Err(EngineError::InternalError(
+                    "fitness errors length mismatch".to_string(),
+                ));
+            };
+            let steps = if body.steps.is_empty() {
+                vec![0.0; genomes.len()]
+            } else if body.steps.len() == genomes.len() {
+                body.steps
+            } else {
+                return Err(EngineError::InternalError(
+                    "fitness steps length mismatch".to_string(),
+                ));
+            };
+            Ok(Scores {
+                fitness: body.fitness,
+                case_errors,
+                steps,
+            })
+        })
+    }
+}
+
+/// Scores genomes in-process with the engine VM, on a dedicated thread pool unless it is
+/// limited to a single thread.
+pub struct NativeEvaluator {
+    pool: Option<Arc<ThreadPool>>,
+}
+
+impl NativeEvaluator {
+    /// `threads` of 0 uses one thread per CPU core.
+    pub fn new(threads: usize) -> Result<Self, EngineError> {
+        if threads == 1 {
+            return Ok(Self { pool: None });
+        }
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|i| format!("native-eval-{i}"))
+            .build()
+            .map_err(|e| EngineError::InternalError(format!("evaluator thread pool: {e}")))?;
+        Ok(Self {
+            pool: Some(Arc::new(pool)),
+        })
+    }
+}
+
+impl FitnessEvaluator for NativeEvaluator {
+    /// Scoring is CPU-bound, so it runs on a blocking thread rather than an async worker.
+    fn score<'a>(&'a self, task: &'a str, genomes: &'a [Genome]) -> ScoreFuture<'a> {
+        let (pool, task, genomes) = (self.pool.clone(), task.to_string(), genomes.to_vec());
+        Box::pin(async move {
+            tokio::task::spawn_blocking(move || match pool {
+                Some(pool) => fitness::score_genomes_parallel(&task, &genomes, &pool),
+                None => fitness::score_genomes(&task, &genomes),
+            })
+            .await
+            .map_err(|e| EngineError::InternalError(format!("native scoring failed: {e}")))
+        })
+    }
+}
+
+/// Deterministic stand-in for tests: shorter genomes score higher.
+#[cfg(test)]
+pub struct MockEvaluator;
+
+#[cfg(test)]
+impl FitnessEvaluator for MockEvaluator {
+    fn score<'a>(&'a self, _task: &'a str, genomes: &'a [Genome]) -> ScoreFuture<'a> {
+        Box::pin(async move {
+            let len = |g: &Genome| g.instructions.len() as f64;
+            Ok(Scores {
+                fitness: genomes.iter().map(|g| 1.0 / (1.0 + len(g))).collect(),
+                case_errors: genomes
+                    .iter()
+                    .map(|g| vec![len(g), len(g) % 3.0])
+                    .collect(),
+                steps: genomes.iter().map(len).collect(),
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+impl Evaluators {
+    pub fn mock() -> Self {
+        let mock: SharedEvaluator = Arc::new(MockEvaluator);
+        Self {
+            http: mock.clone(),
+            native: mock,
+        }
+    }
+}
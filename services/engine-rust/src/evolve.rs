@@ -1,44 +1,354 @@
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Instant;
 
-use rand::{rngs::StdRng, Rng};
-use tokio::sync::Mutex;
+use rand::{Rng, SeedableRng};
+use tokio::sync::{broadcast, Mutex, RwLock};
 
 use crate::error::EngineError;
-use crate::models::{genome, rng, Genome, RunConfig, RunState};
+use crate::models::cache::{FitnessCache, Pending};
+use crate::models::evaluator::{Evaluators, FitnessEvaluator};
+use crate::models::fitness::Scores;
+use crate::models::genome::OpSet;
+use crate::models::islands::{island_ranges, migration_targets, ranked_members};
+use crate::models::nsga::{self, Objectives};
+use crate::models::rng::RunRng;
+use crate::models::selection::Selector;
+use crate::models::stats;
+use crate::models::storage::{RunDb, RunSave, RunWriter};
+use crate::models::{
+    genome, rng, EvolutionMode, Genome, ParetoPoint, RunConfig, RunEvent, RunHistoryPoint,
+    RunSnapshot, RunState, RunStatus, StopReason,
+};
 
-pub type RunStore = Arc<Mutex<HashMap<String, RunInternal>>>;
+pub const SNAPSHOT_VERSION: u32 = 1;
+const MAX_RUN_ID_LEN: usize = 64;
+/// Events buffered per run; subscribers that fall further behind skip ahead.
+const EVENT_BUFFER: usize = 64;
+
+/// In-memory runs, optionally queued for a SQLite database after every change.
+/// The map lock is only held to look runs up or add them; each run has its own locks.
+#[derive(Clone)]
+pub struct RunStore {
+    runs: Arc<RwLock<HashMap<String, Arc<RunEntry>>>>,
+    writer: Option<RunWriter>,
+}
+
+/// One run. A step holds `stepping` for the whole generation, scoring included, so
+/// steps on the same run are linearizable; `state` is only held while reading or
+/// writing, so state queries never wait on the evaluator.
+pub struct RunEntry {
+    stepping: Mutex<()>,
+    state: Mutex<RunInternal>,
+}
+
+impl RunEntry {
+    fn new(run: RunInternal) -> Arc<Self> {
+        Arc::new(Self {
+            stepping: Mutex::new(()),
+            state: Mutex::new(run),
+        })
+    }
+}
+
+impl RunStore {
+    fn with_runs(runs: HashMap<String, RunInternal>, writer: Option<RunWriter>) -> Self {
+        let runs = runs
+            .into_iter()
+            .map(|(id, run)| (id, RunEntry::new(run)))
+            .collect();
+        Self {
+            runs: Arc::new(RwLock::new(runs)),
+            writer,
+        }
+    }
+
+    async fn get(&self, run_id: &str) -> Option<Arc<RunEntry>> {
+        self.runs.read().await.get(run_id).cloned()
+    }
+
+    async fn entry(&self, run_id: &str) -> Result<Arc<RunEntry>, EngineError> {
+        self.get(run_id)
+            .await
+            .ok_or_else(|| EngineError::NotFound("run not found".to_string()))
+    }
+
+    async fn entries(&self) -> Vec<(String, Arc<RunEntry>)> {
+        let runs = self.runs.read().await;
+        runs.iter().map(|(id, e)| (id.clone(), e.clone())).collect()
+    }
+
+    /// Queues the run's snapshot and its not yet queued history points for the database,
+    /// if any. Only the copy is made here, under the run's lock; serializing and writing
+    /// happen on the writer thread, which logs failures rather than returning them since
+    /// the in-memory run stays authoritative.
+    fn persist(&self, run_id: &str, run: &mut RunInternal) {
+        let Some(writer) = &self.writer else {
+            return;
+        };
+        let history = (run.persisted_history..)
+            .zip(run.history[run.persisted_history..].iter().cloned())
+            .collect();
+        writer.save(RunSave {
+            run_id: run_id.to_string(),
+            snapshot: run.snapshot_with(Vec::new()),
+            history,
+        });
+        run.persisted_history = run.history.len();
+    }
+
+    /// Waits for every queued database write to be attempted.
+    pub async fn flush(&self) {
+        if let Some(writer) = &self.writer {
+            writer.flush().await;
+        }
+    }
+}
 
 pub struct RunInternal {
     pub cfg: RunConfig,
     pub generation: u32,
     pub population: Vec<Genome>,
     pub fitness: Vec<f64>,
+    pub case_errors: Vec<Vec<f64>>,
+    pub steps: Vec<f64>,
+    /// Pareto rank and crowding distance per individual; only maintained in NSGA-II mode.
+    pub ranks: Vec<usize>,
+    pub crowding: Vec<f64>,
+    /// Pareto front of the current population, built on the first `/pareto` request.
+    pareto: Option<Vec<ParetoPoint>>,
     pub best_fitness: f64,
     pub best_genome: Genome,
-    pub rng: StdRng,
-    pub history: Vec<(u32, f64)>,
+    pub rng: RunRng,
+    /// One RNG stream per island; empty unless the run uses islands.
+    pub island_rngs: Vec<RunRng>,
+    pub history: Vec<RunHistoryPoint>,
+    /// Leading `history` points already queued for the database.
+    persisted_history: usize,
+    pub ops: OpSet,
+    pub status: RunStatus,
+    pub error: Option<String>,
+    /// Bumped whenever a background runner is spawned; older runners see the change and exit.
+    pub runner_epoch: u64,
+    pub events: broadcast::Sender<RunEvent>,
+    pub stop_reason: Option<StopReason>,
+    pub evaluations: u64,
+    pub elapsed_secs: f64,
+    /// Generation at which `best_fitness` last increased, for the stagnation criterion.
+    pub last_improvement: u32,
+    /// Recent scores by genome. Only the current population's are restored from a snapshot.
+    pub cache: FitnessCache,
+    pub cache_hits: u64,
+    pub duplicates_replaced: u64,
 }
 
 impl RunInternal {
-    pub fn new(cfg: RunConfig) -> Self {
+    pub fn new(cfg: RunConfig) -> Result<Self, EngineError> {
+        let ops = OpSet::from_config(cfg.allowed_ops.as_deref(), cfg.op_weights.as_ref())
+            .map_err(EngineError::BadRequest)?;
         let size = usize::try_from(cfg.population.max(1)).unwrap_or(1);
         let mut rng = rng::seeded_rng(cfg.seed as u64);
-        let population = (0..size).map(|_| genome::random_genome(&mut rng)).collect();
-        Self {
+        let (mut population, island_rngs) = match &cfg.islands {
+            None => {
+                let population = (0..size)
+                    .map(|_| genome::random_genome(&mut rng, &ops))
+                    .collect();
+                (population, Vec::new())
+            }
+            Some(islands) => {
+                let mut island_rngs: Vec<RunRng> = (0..islands.count)
+                    .map(|_| rng::seeded_rng(rng.gen()))
+                    .collect();
+                let mut population = Vec::with_capacity(size);
+                for (range, island_rng) in island_ranges(size, islands.count)
+                    .into_iter()
+                    .zip(island_rngs.iter_mut())
+                {
+                    population.extend(range.map(|_| genome::random_genome(island_rng, &ops)));
+                }
+                (population, island_rngs)
+            }
+        };
+        // Seeds overwrite random individuals after the fact so the RNG stream is unchanged.
+        for (i, text) in cfg.seed_genomes.iter().flatten().enumerate() {
+            let seed = genome::parse_assembly(text)
+                .and_then(|seed| {
+                    genome::check_length(&seed)?;
+                    match seed.instructions.iter().find(|instr| !ops.contains(&instr.op)) {
+                        Some(instr) => Err(format!("{} is not an allowed op", instr.op)),
+                        None => Ok(seed),
+                    }
+                })
+                .map_err(|e| EngineError::BadRequest(format!("seed_genomes[{i}]: {e}")))?;
+            match population.get_mut(i) {
+                Some(slot) => *slot = seed,
+                None => {
+                    return Err(EngineError::BadRequest(
+                        "more seed_genomes than population".to_string(),
+                    ))
+                }
+            }
+        }
+        Ok(Self {
             cfg,
             generation: 0,
             population,
             fitness: Vec::new(),
+            case_errors: Vec::new(),
+            steps: Vec::new(),
+            ranks: Vec::new(),
+            crowding: Vec::new(),
+            pareto: None,
             best_fitness: 0.0,
             best_genome: Genome {
                 instructions: Vec::new(),
             },
             rng,
+            island_rngs,
             history: Vec::new(),
+            persisted_history: 0,
+            ops,
+            status: RunStatus::Idle,
+            error: None,
+            runner_epoch: 0,
+            events: broadcast::channel(EVENT_BUFFER).0,
+            stop_reason: None,
+            evaluations: 0,
+            elapsed_secs: 0.0,
+            last_improvement: 0,
+            cache: FitnessCache::for_population(size),
+            cache_hits: 0,
+            duplicates_replaced: 0,
+        })
+    }
+
+    pub fn to_snapshot(&self) -> RunSnapshot {
+        self.snapshot_with(self.history.clone())
+    }
+
+    /// Snapshot carrying `history` in place of the run's own, which the database stores
+    /// separately.
+    fn snapshot_with(&self, history: Vec<RunHistoryPoint>) -> RunSnapshot {
+        RunSnapshot {
+            version: SNAPSHOT_VERSION,
+            run_id: None,
+            config: self.cfg.clone(),
+            generation: self.generation,
+            population: self.population.clone(),
+            fitness: self.fitness.clone(),
+            case_errors: self
+                .case_errors
+                .iter()
+                .map(|row| row.iter().map(|&e| e.is_finite().then_some(e)).collect())
+                .collect(),
+            steps: self.steps.clone(),
+            best_fitness: self.best_fitness,
+            best_genome: self.best_genome.clone(),
+            history,
+            rng: self.rng.clone(),
+            island_rngs: self.island_rngs.clone(),
+            status: self.status,
+            error: self.error.clone(),
+            stop_reason: self.stop_reason,
+            evaluations: self.evaluations,
+            elapsed_secs: self.elapsed_secs,
+            cache_hits: self.cache_hits,
+            duplicates_replaced: self.duplicates_replaced,
         }
     }
 
+    pub fn from_snapshot(snapshot: RunSnapshot) -> Result<Self, EngineError> {
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(EngineError::BadRequest(format!(
+                "unsupported snapshot version {}",
+                snapshot.version
+            )));
+        }
+        let ops = OpSet::from_config(
+            snapshot.config.allowed_ops.as_deref(),
+            snapshot.config.op_weights.as_ref(),
+        )
+        .map_err(EngineError::BadRequest)?;
+        let size = snapshot.population.len();
+        if size == 0
+            || i64::try_from(size) != Ok(snapshot.config.population)
+            || snapshot.fitness.len() != size
+            || snapshot.steps.len() != size
+            || (!snapshot.case_errors.is_empty() && snapshot.case_errors.len() != size)
+        {
+            return Err(EngineError::BadRequest(
+                "snapshot population and score lengths disagree".to_string(),
+            ));
+        }
+        if snapshot
+            .history
+            .iter()
+            .any(|point| point.generation > snapshot.generation)
+        {
+            return Err(EngineError::BadRequest(
+                "snapshot history is ahead of its generation".to_string(),
+            ));
+        }
+        for genome in snapshot.population.iter().chain([&snapshot.best_genome]) {
+            genome::check_length(genome)
+                .map_err(|e| EngineError::BadRequest(format!("snapshot {e}")))?;
+        }
+        let island_count = snapshot.config.islands.map_or(0, |i| i.count);
+        if snapshot.island_rngs.len() != island_count {
+            return Err(EngineError::BadRequest(
+                "snapshot island RNG count does not match islands.count".to_string(),
+            ));
+        }
+
+        let improved_at = last_improvement(&snapshot.history);
+        let mut run = Self {
+            cfg: snapshot.config,
+            generation: snapshot.generation,
+            population: snapshot.population,
+            fitness: snapshot.fitness,
+            case_errors: snapshot
+                .case_errors
+                .into_iter()
+                .map(|row| row.into_iter().map(|e| e.unwrap_or(f64::INFINITY)).collect())
+                .collect(),
+            steps: snapshot.steps,
+            ranks: Vec::new(),
+            crowding: Vec::new(),
+            pareto: None,
+            best_fitness: snapshot.best_fitness,
+            best_genome: snapshot.best_genome,
+            rng: snapshot.rng,
+            island_rngs: snapshot.island_rngs,
+            history: snapshot.history,
+            persisted_history: 0,
+            ops,
+            status: snapshot.status,
+            error: snapshot.error,
+            runner_epoch: 0,
+            events: broadcast::channel(EVENT_BUFFER).0,
+            stop_reason: snapshot.stop_reason,
+            evaluations: snapshot.evaluations,
+            elapsed_secs: snapshot.elapsed_secs,
+            last_improvement: improved_at,
+            cache: FitnessCache::for_population(size),
+            cache_hits: snapshot.cache_hits,
+            duplicates_replaced: snapshot.duplicates_replaced,
+        };
+        run.cache.insert_scored(
+            &run.cfg.task,
+            &run.population,
+            &run.fitness,
+            &run.case_errors,
+            &run.steps,
+        );
+        if run.cfg.mode == EvolutionMode::Nsga2 {
+            let (ranks, crowding) = nsga::rank_and_crowding(&run.objectives());
+            run.ranks = ranks;
+            run.crowding = crowding;
+        }
+        Ok(run)
+    }
+
     pub fn to_state(&self, run_id: &str) -> RunState {
         RunState {
             run_id: run_id.to_string(),
@@ -50,108 +360,610 @@ impl RunInternal {
             generations: self.cfg.generations as u32,
             mutation_rate: self.cfg.mutation_rate as f32,
             task: self.cfg.task.clone(),
+            evaluator: self.cfg.evaluator,
+            allowed_ops: self.cfg.allowed_ops.clone(),
+            op_weights: self.cfg.op_weights.clone(),
+            crossover_rate: self.cfg.crossover_rate,
+            crossover: self.cfg.crossover,
+            selection: self.cfg.selection,
+            mode: self.cfg.mode,
+            islands: self.cfg.islands,
+            island_best_fitness: self.island_best_fitness(),
+            stop: self.cfg.stop,
+            status: self.status,
+            error: self.error.clone(),
+            stop_reason: self.stop_reason,
+            evaluations: self.evaluations,
+            elapsed_secs: self.elapsed_secs,
+            cache_hits: self.cache_hits,
+            cache_hit_rate: self.cache_hits as f64
+                / (self.cache_hits + self.evaluations).max(1) as f64,
+            replace_duplicates: self.cfg.replace_duplicates,
+            unique_count: self.history.last().map_or(0, |p| p.unique_count),
+            duplicates_replaced: self.duplicates_replaced,
         }
     }
 
-    pub fn apply_fitness(&mut self, fitness: Vec<f64>) {
-        self.fitness = fitness;
+    /// Sends the current progress to subscribers; having none is not an error.
+    fn publish(&self, run_id: &str) {
+        let _ = self.events.send(RunEvent {
+            run_id: run_id.to_string(),
+            generation: self.generation,
+            best_fitness: self.best_fitness,
+            mean_fitness: self.history.last().map_or(0.0, |p| p.mean_fitness),
+            best_genome: self.best_genome.clone(),
+            status: self.status,
+        });
+    }
+
+    /// `RunConfig.generations` as a generation count; the run is finished once it gets there.
+    pub fn generation_limit(&self) -> u32 {
+        u32::try_from(self.cfg.generations).unwrap_or(u32::MAX)
+    }
+
+    /// The first stopping criterion the run currently meets, if any.
+    pub fn met_stop_criterion(&self) -> Option<StopReason> {
+        let stop = &self.cfg.stop;
+        if stop.target_fitness.is_some_and(|t| self.best_fitness >= t) {
+            Some(StopReason::TargetFitness)
+        } else if self.generation >= self.generation_limit() {
+            Some(StopReason::GenerationLimit)
+        } else if stop
+            .stagnation_generations
+            .is_some_and(|n| self.generation.saturating_sub(self.last_improvement) >= n)
+        {
+            Some(StopReason::Stagnation)
+        } else if stop.max_evaluations.is_some_and(|n| self.evaluations >= n) {
+            Some(StopReason::MaxEvaluations)
+        } else if stop.max_wall_time_secs.is_some_and(|t| self.elapsed_secs >= t) {
+            Some(StopReason::MaxWallTime)
+        } else {
+            None
+        }
+    }
+
+    /// Finishes the run once a stopping criterion is met, keeping the first reason.
+    fn check_stop(&mut self) {
+        if self.stop_reason.is_none() {
+            self.stop_reason = self.met_stop_criterion();
+        }
+        if self.stop_reason.is_some() {
+            self.status = RunStatus::Finished;
+        }
+    }
+
+    pub fn island_best_fitness(&self) -> Option<Vec<f64>> {
+        let islands = self.cfg.islands.as_ref()?;
+        let ranges = island_ranges(self.population.len(), islands.count);
+        Some(
+            ranges
+                .into_iter()
+                .map(|r| {
+                    self.fitness
+                        .get(r)
+                        .and_then(best_index)
+                        .map_or(0.0, |(_, best)| best)
+                })
+                .collect(),
+        )
+    }
+
+    pub fn apply_fitness(&mut self, scores: Scores) {
+        self.apply_ranked_fitness(scores, None);
+    }
+
+    /// As [`Self::apply_fitness`], with the population's NSGA-II ranks and crowding
+    /// distances if already known.
+    fn apply_ranked_fitness(&mut self, scores: Scores, ranking: Option<(Vec<usize>, Vec<f64>)>) {
+        self.fitness = scores.fitness;
+        self.case_errors = scores.case_errors;
+        self.steps = scores.steps;
+        self.pareto = None;
+        if self.cfg.mode == EvolutionMode::Nsga2 {
+            let (ranks, crowding) =
+                ranking.unwrap_or_else(|| nsga::rank_and_crowding(&self.objectives()));
+            self.ranks = ranks;
+            self.crowding = crowding;
+        }
         if let Some((idx, best)) = best_index(&self.fitness) {
+            if best > self.best_fitness || self.history.is_empty() {
+                self.last_improvement = self.generation;
+            }
             self.best_fitness = best;
             self.best_genome = self.population[idx].clone();
         }
-        self.history.push((self.generation, self.best_fitness));
+        self.history.push(stats::generation_stats(
+            self.generation,
+            &self.population,
+            &self.fitness,
+            self.best_fitness,
+        ));
+    }
+
+    /// Adds how the generation just scored was produced to the run's statistics: cache
+    /// hits out of `scored` genomes and the duplicates replaced beforehand.
+    fn record_generation(&mut self, scored: usize, hits: usize, replaced: usize) {
+        self.cache_hits += hits as u64;
+        self.duplicates_replaced += replaced as u64;
+        if let Some(point) = self.history.last_mut() {
+            point.cache_hit_rate = hits as f64 / scored.max(1) as f64;
+            point.duplicates_replaced = replaced as u32;
+        }
+    }
+
+    /// With `replace_duplicates` set, swaps repeated genomes for random ones. Islands are
+    /// deduplicated separately, each with its own RNG, so a migrant may live on several.
+    fn replace_duplicates(&mut self, population: &mut [Genome]) -> usize {
+        if !self.cfg.replace_duplicates {
+            return 0;
+        }
+        match &self.cfg.islands {
+            None => genome::replace_duplicates(population, &mut self.rng, &self.ops),
+            Some(islands) => island_ranges(population.len(), islands.count)
+                .into_iter()
+                .zip(self.island_rngs.iter_mut())
+                .map(|(range, rng)| {
+                    genome::replace_duplicates(&mut population[range], rng, &self.ops)
+                })
+                .sum(),
+        }
     }
 
     pub fn next_population(&mut self) -> Vec<Genome> {
         let pop_size = self.population.len();
         let mut new_pop = Vec::with_capacity(pop_size);
-        // Elitism
-        new_pop.push(self.best_genome.clone());
 
-        while new_pop.len() < pop_size {
-            let parent_idx = self.tournament_select(3);
-            let mut child = self.population[parent_idx].clone();
-            if self.rng.gen::<f64>() < self.cfg.mutation_rate {
-                genome::mutate_genome(&mut child, &mut self.rng);
+        match self.cfg.mode {
+            EvolutionMode::Single => match &self.cfg.islands {
+                None => {
+                    // Elitism
+                    new_pop.push(self.best_genome.clone());
+
+                    let mut selector =
+                        Selector::new(&self.cfg.selection, &self.fitness, &self.case_errors);
+                    while new_pop.len() < pop_size {
+                        new_pop.push(breed(
+                            &self.population,
+                            &self.cfg,
+                            &self.ops,
+                            &mut self.rng,
+                            |rng| selector.select(rng),
+                        ));
+                    }
+                }
+                Some(islands) => {
+                    // Each island breeds only from itself, with its own elite and RNG stream.
+                    let ranges = island_ranges(pop_size, islands.count);
+                    for (range, island_rng) in ranges.into_iter().zip(self.island_rngs.iter_mut()) {
+                        let population = &self.population[range.clone()];
+                        let fitness = self.fitness.get(range.clone()).unwrap_or(&[]);
+                        let case_errors = self.case_errors.get(range.clone()).unwrap_or(&[]);
+                        let elite = best_index(fitness).map_or(0, |(idx, _)| idx);
+                        new_pop.push(population[elite].clone());
+
+                        let mut selector = Selector::new(&self.cfg.selection, fitness, case_errors);
+                        while new_pop.len() < range.end {
+                            new_pop.push(breed(
+                                population,
+                                &self.cfg,
+                                &self.ops,
+                                island_rng,
+                                |rng| selector.select(rng),
+                            ));
+                        }
+                    }
+                }
+            },
+            EvolutionMode::Nsga2 => {
+                // Parents survive through the merge in `advance`, so no explicit elite here.
+                let (ranks, crowding) = (&self.ranks, &self.crowding);
+                while new_pop.len() < pop_size {
+                    new_pop.push(breed(
+                        &self.population,
+                        &self.cfg,
+                        &self.ops,
+                        &mut self.rng,
+                        |rng| nsga::crowded_tournament(ranks, crowding, rng),
+                    ));
+                }
             }
-            new_pop.push(child);
         }
 
         new_pop
     }
 
-    fn tournament_select(&mut self, k: usize) -> usize {
-        let mut best_idx = 0;
-        let mut best_fit = f64::MIN;
-        let len = self.population.len();
-        for _ in 0..k {
-            let idx = self.rng.gen_range(0..len);
-            let fit = *self.fitness.get(idx).unwrap_or(&0.0);
-            if fit > best_fit {
-                best_fit = fit;
-                best_idx = idx;
+    /// Installs a scored offspring population as the next generation. In NSGA-II mode
+    /// `survivors` are those of the parents followed by `offspring`, as picked by
+    /// [`nsga::rank_survivors`]; they are computed here if not given.
+    pub fn advance(
+        &mut self,
+        offspring: Vec<Genome>,
+        scores: Scores,
+        survivors: Option<nsga::Survivors>,
+    ) {
+        self.generation += 1;
+        match self.cfg.mode {
+            EvolutionMode::Single => {
+                self.population = offspring;
+                self.apply_fitness(scores);
+                self.migrate();
+            }
+            EvolutionMode::Nsga2 => {
+                let pop_size = self.population.len();
+                let mut combined_scores = Scores {
+                    fitness: std::mem::take(&mut self.fitness),
+                    case_errors: std::mem::take(&mut self.case_errors),
+                    steps: std::mem::take(&mut self.steps),
+                };
+                combined_scores.extend(scores);
+                let mut combined = std::mem::take(&mut self.population);
+                combined.extend(offspring);
+
+                let survivors = survivors.unwrap_or_else(|| {
+                    nsga::rank_survivors(&objectives(&combined, &combined_scores), pop_size)
+                });
+                self.population = survivors
+                    .indices
+                    .iter()
+                    .map(|&i| combined[i].clone())
+                    .collect();
+                self.apply_ranked_fitness(
+                    combined_scores.select(&survivors.indices),
+                    Some((survivors.ranks, survivors.crowding)),
+                );
+            }
+        }
+    }
+
+    /// Copies each island's best individuals over the worst of its target island,
+    /// every `migration_interval` generations.
+    fn migrate(&mut self) {
+        let Some(islands) = self.cfg.islands else {
+            return;
+        };
+        let interval = islands.migration_interval.max(1);
+        if islands.count < 2 || !self.generation.is_multiple_of(interval) {
+            return;
+        }
+        let ranges = island_ranges(self.population.len(), islands.count);
+        let targets = migration_targets(&islands, &mut self.rng);
+
+        // Pick all emigrants before anyone arrives so migrants move only one hop.
+        let current = Scores {
+            fitness: self.fitness.clone(),
+            case_errors: self.case_errors.clone(),
+            steps: self.steps.clone(),
+        };
+        let emigrants: Vec<(Vec<Genome>, Scores)> = ranges
+            .iter()
+            .map(|range| {
+                let picked = ranked_members(&self.fitness, range, islands.migrants, false);
+                let genomes = picked.iter().map(|&i| self.population[i].clone()).collect();
+                (genomes, current.select(&picked))
+            })
+            .collect();
+
+        for (source, (genomes, scores)) in emigrants.into_iter().enumerate() {
+            let target = &ranges[targets[source]];
+            let slots = ranked_members(&self.fitness, target, genomes.len(), true);
+            for (j, (slot, genome)) in slots.into_iter().zip(genomes).enumerate() {
+                self.population[slot] = genome;
+                self.fitness[slot] = scores.fitness[j];
+                if let Some(e) = self.case_errors.get_mut(slot) {
+                    *e = scores.case_errors[j].clone();
+                }
+                if let Some(s) = self.steps.get_mut(slot) {
+                    *s = scores.steps[j];
+                }
             }
         }
-        best_idx
+    }
+
+    pub fn objectives(&self) -> Vec<Objectives> {
+        objectives(
+            &self.population,
+            &Scores {
+                fitness: self.fitness.clone(),
+                case_errors: Vec::new(),
+                steps: self.steps.clone(),
+            },
+        )
+    }
+
+    /// Non-dominated individuals of the current population, one per distinct objective
+    /// vector, ordered by error. NSGA-II runs reuse the ranks from the last generation;
+    /// otherwise the front is sorted once per generation.
+    pub fn pareto_front(&mut self) -> Vec<ParetoPoint> {
+        if let Some(points) = &self.pareto {
+            return points.clone();
+        }
+        let objs = self.objectives();
+        let front: Vec<usize> = if self.ranks.len() == objs.len() {
+            (0..objs.len()).filter(|&i| self.ranks[i] == 0).collect()
+        } else {
+            nsga::non_dominated_sort(&objs)
+                .into_iter()
+                .next()
+                .unwrap_or_default()
+        };
+        let mut points: Vec<ParetoPoint> = Vec::new();
+        for idx in front {
+            let o = objs[idx];
+            let duplicate = points.iter().any(|p| {
+                p.error == o.error() && p.length as f64 == o.length() && p.steps == o.steps()
+            });
+            if duplicate {
+                continue;
+            }
+            points.push(ParetoPoint {
+                genome: self.population[idx].clone(),
+                fitness: self.fitness[idx],
+                error: o.error(),
+                length: o.length() as u32,
+                steps: o.steps(),
+            });
+        }
+        points.sort_by(|a, b| {
+            a.error
+                .partial_cmp(&b.error)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then(a.length.cmp(&b.length))
+        });
+        self.pareto = Some(points.clone());
+        points
     }
 }
 
+fn objectives(population: &[Genome], scores: &Scores) -> Vec<Objectives> {
+    population
+        .iter()
+        .enumerate()
+        .map(|(i, g)| {
+            let fitness = scores.fitness.get(i).copied().unwrap_or(0.0);
+            let steps = scores.steps.get(i).copied().unwrap_or(0.0);
+            Objectives::new(fitness, g, steps)
+        })
+        .collect()
+}
+
+/// Produces one child from parents chosen by `pick`, applying crossover and mutation.
+fn breed(
+    population: &[Genome],
+    cfg: &RunConfig,
+    ops: &OpSet,
+    rng: &mut RunRng,
+    mut pick: impl FnMut(&mut RunRng) -> usize,
+) -> Genome {
+    let parent_idx = pick(rng);
+    // Only draw for crossover when enabled so seeded runs without it stay reproducible.
+    let mut child = if cfg.crossover_rate > 0.0 && rng.gen::<f64>() < cfg.crossover_rate {
+        let other_idx = pick(rng);
+        genome::crossover(
+            &population[parent_idx],
+            &population[other_idx],
+            cfg.crossover,
+            rng,
+        )
+    } else {
+        population[parent_idx].clone()
+    };
+    if rng.gen::<f64>() < cfg.mutation_rate {
+        genome::mutate_genome(&mut child, rng, ops);
+    }
+    child
+}
+
 pub fn new_store() -> RunStore {
-    Arc::new(Mutex::new(HashMap::new()))
+    RunStore::with_runs(HashMap::new(), None)
+}
+
+/// Store backed by `db`, preloaded with every run it holds. Rows that no longer
+/// decode are skipped with a warning rather than failing startup.
+pub fn open_store(db: Arc<RunDb>) -> Result<RunStore, EngineError> {
+    let mut runs = HashMap::new();
+    for (run_id, json) in db.load_all()? {
+        let restored = db.load_history(&run_id).and_then(|points| {
+            let mut snapshot = serde_json::from_str::<RunSnapshot>(&json)
+                .map_err(|e| EngineError::BadRequest(e.to_string()))?;
+            let stored = points.len();
+            snapshot.history = points
+                .iter()
+                .map(|point| serde_json::from_str(point))
+                .collect::<Result<_, _>>()
+                .map_err(|e| EngineError::BadRequest(e.to_string()))?;
+            let mut run = RunInternal::from_snapshot(snapshot)?;
+            run.persisted_history = stored;
+            Ok(run)
+        });
+        match restored {
+            Ok(run) => {
+                runs.insert(run_id, run);
+            }
+            Err(err) => tracing::warn!("skipping stored run {}: {}", run_id, err),
+        }
+    }
+    tracing::info!("loaded {} runs from database", runs.len());
+    Ok(RunStore::with_runs(runs, Some(RunWriter::spawn(db))))
 }
 
 pub async fn create_run(
     cfg: RunConfig,
     runs: &RunStore,
-    fitness_url: &str,
+    evaluators: &Evaluators,
 ) -> Result<String, EngineError> {
-    let mut run = RunInternal::new(cfg);
-    let scores = score_population(&run.cfg.task, &run.population, fitness_url).await?;
+    let started = Instant::now();
+    let mut run = RunInternal::new(cfg)?;
+    let mut population = std::mem::take(&mut run.population);
+    let replaced = run.replace_duplicates(&mut population);
+    run.population = population;
+    let evaluator = evaluators.get(run.cfg.evaluator);
+    let pending = run.cache.lookup(&run.cfg.task, &run.population);
+    let fresh = score_misses(evaluator.as_ref(), &run.cfg.task, &pending).await?;
+    let (hits, misses) = (pending.hits(), pending.misses.len());
+    let scores = run.cache.complete(pending, fresh)?;
     run.apply_fitness(scores);
+    run.record_generation(run.population.len(), hits, replaced);
+    run.evaluations = misses as u64;
+    run.elapsed_secs = started.elapsed().as_secs_f64();
+    run.check_stop();
     let run_id = generate_run_id(&mut run.rng);
     let task = run.cfg.task.clone();
     let pop = run.cfg.population;
 
-    let mut guard = runs.lock().await;
-    guard.insert(run_id.clone(), run);
+    runs.persist(&run_id, &mut run);
+    runs.runs
+        .write()
+        .await
+        .insert(run_id.clone(), RunEntry::new(run));
     tracing::info!("run created id={} task={} pop={}", run_id, task, pop);
     Ok(run_id)
 }
 
+/// Snapshot of a run, labelled with its id, for export.
+pub async fn get_snapshot(runs: &RunStore, run_id: &str) -> Option<RunSnapshot> {
+    let entry = runs.get(run_id).await?;
+    let run = entry.state.lock().await;
+    Some(RunSnapshot {
+        run_id: Some(run_id.to_string()),
+        ..run.to_snapshot()
+    })
+}
+
+/// Recreates an exported run. The snapshot's id is kept unless it is missing; a fresh id
+/// is drawn from entropy so the run's own RNG stream is left untouched.
+pub async fn import_run(snapshot: RunSnapshot, runs: &RunStore) -> Result<String, EngineError> {
+    let requested = snapshot.run_id.clone();
+    let mut run = RunInternal::from_snapshot(snapshot)?;
+    // Background runners belong to the exporting engine; the copy starts out idle.
+    if matches!(run.status, RunStatus::Running | RunStatus::Paused) {
+        run.status = RunStatus::Idle;
+    }
+    let entry = RunEntry::new(run);
+    let run_id = {
+        let mut map = runs.runs.write().await;
+        let run_id = match requested {
+            Some(id) if !is_valid_run_id(&id) => {
+                return Err(EngineError::BadRequest(format!(
+                    "run_id must be 1 to {MAX_RUN_ID_LEN} letters, digits, '-' or '_'"
+                )));
+            }
+            Some(id) if map.contains_key(&id) => {
+                return Err(EngineError::BadRequest(format!("run {} already exists", id)));
+            }
+            Some(id) => id,
+            None => loop {
+                let id = generate_run_id(&mut RunRng::from_entropy());
+                if !map.contains_key(&id) {
+                    break id;
+                }
+            },
+        };
+        map.insert(run_id.clone(), entry.clone());
+        run_id
+    };
+    let mut run = entry.state.lock().await;
+    runs.persist(&run_id, &mut run);
+    tracing::info!("run imported id={} gen={}", run_id, run.generation);
+    Ok(run_id)
+}
+
 pub async fn get_run_state(runs: &RunStore, run_id: &str) -> Option<RunState> {
-    let guard = runs.lock().await;
-    guard.get(run_id).map(|r| r.to_state(run_id))
+    let entry = runs.get(run_id).await?;
+    let run = entry.state.lock().await;
+    Some(run.to_state(run_id))
 }
 
 pub async fn step_run(
     runs: &RunStore,
     run_id: &str,
-    fitness_url: &str,
+    evaluators: &Evaluators,
 ) -> Result<RunState, EngineError> {
-    let (new_population, cfg_task, pop_size) = {
-        let mut guard = runs.lock().await;
-        let run = guard
-            .get_mut(run_id)
-            .ok_or_else(|| EngineError::NotFound("run not found".to_string()))?;
-        let new_pop = run.next_population();
+    step(runs, run_id, evaluators, None)
+        .await?
+        .ok_or_else(|| EngineError::InternalError("step was skipped".to_string()))
+}
+
+/// Runs one generation. `runner` is the epoch of the background runner calling it, or
+/// `None` for a client request; runners get `Ok(None)` once they should stop.
+async fn step(
+    runs: &RunStore,
+    run_id: &str,
+    evaluators: &Evaluators,
+    runner: Option<u64>,
+) -> Result<Option<RunState>, EngineError> {
+    let entry = runs.entry(run_id).await?;
+    let _stepping = entry.stepping.lock().await;
+    let started = Instant::now();
+    let (new_population, replaced, pending, cfg_task, evaluator, pop_size, rngs, parents) = {
+        let mut run = entry.state.lock().await;
+        match runner {
+            Some(epoch) => {
+                if run.status != RunStatus::Running || run.runner_epoch != epoch {
+                    return Ok(None);
+                }
+            }
+            None => {
+                if run.status == RunStatus::Running {
+                    return Err(EngineError::BadRequest(
+                        "run is advancing in the background; pause it first".to_string(),
+                    ));
+                }
+            }
+        }
+        run.check_stop();
+        if let Some(reason) = run.stop_reason {
+            return match runner {
+                Some(_) => Ok(None),
+                None => Err(EngineError::BadRequest(format!(
+                    "run has {}",
+                    reason.description()
+                ))),
+            };
+        }
+        let rngs = (run.rng.clone(), run.island_rngs.clone());
+        let mut new_pop = run.next_population();
+        let replaced = run.replace_duplicates(&mut new_pop);
         let task = run.cfg.task.clone();
-        (new_pop, task, run.population.len())
+        let pending = run.cache.lookup(&task, &new_pop);
+        let evaluator = evaluators.get(run.cfg.evaluator).clone();
+        // Parents only change in a step, so these still hold once the offspring are scored.
+        let parents = (run.cfg.mode == EvolutionMode::Nsga2).then(|| run.objectives());
+        let pop_size = run.population.len();
+        (new_pop, replaced, pending, task, evaluator, pop_size, rngs, parents)
     };
 
-    let scores = score_population(&cfg_task, &new_population, fitness_url).await?;
+    let fresh = score_misses(evaluator.as_ref(), &cfg_task, &pending).await?;
+    let survivors = match parents {
+        Some(parents) => {
+            let offspring = pending.scores(&fresh)?;
+            Some(nsga_survivors(parents, &new_population, &offspring, pop_size).await?)
+        }
+        None => None,
+    };
 
-    let mut guard = runs.lock().await;
-    let run = guard
-        .get_mut(run_id)
-        .ok_or_else(|| EngineError::NotFound("run not found".to_string()))?;
+    let mut run = entry.state.lock().await;
+    if let Some(epoch) = runner {
+        if run.status != RunStatus::Running || run.runner_epoch != epoch {
+            // Paused, cancelled or replaced while scoring: drop the generation and rewind
+            // the RNGs, so nothing lands after the pause and the next step redraws it.
+            (run.rng, run.island_rngs) = rngs;
+            return Ok(None);
+        }
+    }
     if new_population.len() != pop_size {
         return Err(EngineError::InternalError(
             "population size mismatch".to_string(),
         ));
     }
-    run.population = new_population;
-    run.generation += 1;
-    run.apply_fitness(scores);
+    let (hits, misses, scored) = (pending.hits(), pending.misses.len(), new_population.len());
+    let scores = run.cache.complete(pending, fresh)?;
+    run.evaluations += misses as u64;
+    run.advance(new_population, scores, survivors);
+    run.record_generation(scored, hits, replaced);
+    run.elapsed_secs += started.elapsed().as_secs_f64();
+    run.check_stop();
+    runs.persist(run_id, &mut run);
+    run.publish(run_id);
     tracing::info!(
         "generation step run_id={} gen={} best_fitness={}",
         run_id,
@@ -159,62 +971,201 @@ pub async fn step_run(
         run.best_fitness
     );
 
-    Ok(run.to_state(run_id))
+    Ok(Some(run.to_state(run_id)))
+}
+
+/// NSGA-II survivors of `parents` followed by `offspring`. The front sort is quadratic in
+/// the merged size, so it runs on a blocking thread rather than an async worker.
+async fn nsga_survivors(
+    mut parents: Vec<Objectives>,
+    offspring: &[Genome],
+    scores: &Scores,
+    pop_size: usize,
+) -> Result<nsga::Survivors, EngineError> {
+    parents.extend(objectives(offspring, scores));
+    tokio::task::spawn_blocking(move || nsga::rank_survivors(&parents, pop_size))
+        .await
+        .map_err(|e| EngineError::InternalError(format!("survivor selection failed: {e}")))
+}
+
+/// Scores the genomes a cache lookup missed; a fully cached population skips the evaluator.
+async fn score_misses(
+    evaluator: &dyn FitnessEvaluator,
+    task: &str,
+    pending: &Pending,
+) -> Result<Scores, EngineError> {
+    if pending.misses.is_empty() {
+        return Ok(Scores::default());
+    }
+    evaluator.score(task, &pending.misses).await
 }
 
 pub async fn advance_run(
     runs: &RunStore,
     run_id: &str,
     steps: u32,
-    fitness_url: &str,
+    evaluators: &Evaluators,
 ) -> Result<RunState, EngineError> {
-    let mut last_state = None;
+    let mut last_state: Option<RunState> = None;
     for _ in 0..steps {
-        last_state = Some(step_run(runs, run_id, fitness_url).await?);
+        if last_state.as_ref().is_some_and(|s| s.status == RunStatus::Finished) {
+            break;
+        }
+        last_state = Some(step_run(runs, run_id, evaluators).await?);
     }
     last_state.ok_or_else(|| EngineError::InternalError("no steps executed".to_string()))
 }
 
-pub async fn get_history(
+/// Starts evolving the run in a background task until it finishes, fails or is paused.
+pub async fn start_run(
     runs: &RunStore,
     run_id: &str,
-) -> Option<Vec<(u32, f64)>> {
-    let guard = runs.lock().await;
-    guard.get(run_id).map(|r| r.history.clone())
+    evaluators: &Evaluators,
+) -> Result<RunState, EngineError> {
+    set_status(runs, run_id, Some(evaluators), |status| match status {
+        RunStatus::Idle | RunStatus::Failed => Ok(RunStatus::Running),
+        RunStatus::Running | RunStatus::Paused => Err("run has already been started"),
+        RunStatus::Finished => Err("run has finished"),
+    })
+    .await
 }
 
-pub async fn score_population(
-    task: &str,
-    genomes: &[Genome],
-    fitness_url: &str,
-) -> Result<Vec<f64>, EngineError> {
-    #[derive(serde::Deserialize)]
-    struct ScoreResponse {
-        fitness: Vec<f64>,
-    }
-
-    let url = format!("{}/score", fitness_url.trim_end_matches('/'));
-    let client = reqwest::Client::new();
-    let resp = client
-        .post(url)
-        .json(&serde_json::json!({ "task": task, "genomes": genomes }))
-        .send()
-        .await
-        .map_err(|e| EngineError::InternalError(format!("fitness request failed: {e}")))?;
+pub async fn pause_run(runs: &RunStore, run_id: &str) -> Result<RunState, EngineError> {
+    set_status(runs, run_id, None, |status| match status {
+        RunStatus::Running => Ok(RunStatus::Paused),
+        _ => Err("run is not running"),
+    })
+    .await
+}
 
-    let resp = resp
-        .error_for_status()
-        .map_err(|e| EngineError::InternalError(format!("fitness status error: {e}")))?;
+pub async fn resume_run(
+    runs: &RunStore,
+    run_id: &str,
+    evaluators: &Evaluators,
+) -> Result<RunState, EngineError> {
+    set_status(runs, run_id, Some(evaluators), |status| match status {
+        RunStatus::Paused => Ok(RunStatus::Running),
+        _ => Err("run is not paused"),
+    })
+    .await
+}
 
-    let body: ScoreResponse = resp
-        .json()
-        .await
-        .map_err(|e| EngineError::InternalError(format!("fitness decode failed: {e}")))?;
+/// Stops background evolution; the run keeps its progress and can be stepped or restarted.
+pub async fn cancel_run(runs: &RunStore, run_id: &str) -> Result<RunState, EngineError> {
+    set_status(runs, run_id, None, |status| match status {
+        RunStatus::Running | RunStatus::Paused => Ok(RunStatus::Idle),
+        _ => Err("run is not running or paused"),
+    })
+    .await
+}
 
-    Ok(body.fitness)
+/// Respawns runners for runs that were running when the engine last stopped.
+pub async fn resume_background_runs(runs: &RunStore, evaluators: &Evaluators) {
+    for (run_id, entry) in runs.entries().await {
+        let mut run = entry.state.lock().await;
+        if run.status == RunStatus::Running {
+            run.runner_epoch += 1;
+            spawn_runner(runs, &run_id, evaluators, run.runner_epoch);
+        }
+    }
 }
 
-fn generate_run_id(rng: &mut StdRng) -> String {
+/// Applies a status transition; `evaluators` are needed only by transitions into `running`.
+async fn set_status(
+    runs: &RunStore,
+    run_id: &str,
+    evaluators: Option<&Evaluators>,
+    transition: impl FnOnce(RunStatus) -> Result<RunStatus, &'static str>,
+) -> Result<RunState, EngineError> {
+    let entry = runs.entry(run_id).await?;
+    let mut run = entry.state.lock().await;
+    let next = transition(run.status).map_err(|e| EngineError::BadRequest(e.to_string()))?;
+    run.status = next;
+    if let (RunStatus::Running, Some(evaluators)) = (next, evaluators) {
+        // A fresh runner per start/resume; any previous one sees the new epoch and exits.
+        run.error = None;
+        run.runner_epoch += 1;
+        spawn_runner(runs, run_id, evaluators, run.runner_epoch);
+    }
+    runs.persist(run_id, &mut run);
+    run.publish(run_id);
+    tracing::info!("run {} status={:?}", run_id, next);
+    Ok(run.to_state(run_id))
+}
+
+fn spawn_runner(runs: &RunStore, run_id: &str, evaluators: &Evaluators, epoch: u64) {
+    let (runs, run_id, evaluators) = (runs.clone(), run_id.to_string(), evaluators.clone());
+    tokio::spawn(async move {
+        loop {
+            match step(&runs, &run_id, &evaluators, Some(epoch)).await {
+                Ok(Some(_)) => tokio::task::yield_now().await,
+                Ok(None) => break,
+                Err(err) => {
+                    tracing::error!("background run {} failed: {}", run_id, err);
+                    if let Some(entry) = runs.get(&run_id).await {
+                        let mut run = entry.state.lock().await;
+                        if run.runner_epoch == epoch && run.status == RunStatus::Running {
+                            run.status = RunStatus::Failed;
+                            run.error = Some(err.to_string());
+                            runs.persist(&run_id, &mut run);
+                            run.publish(&run_id);
+                        }
+                    }
+                    break;
+                }
+            }
+        }
+    });
+}
+
+/// Live progress of a run; see [`RunEvent`].
+pub async fn subscribe(runs: &RunStore, run_id: &str) -> Option<broadcast::Receiver<RunEvent>> {
+    let entry = runs.get(run_id).await?;
+    let run = entry.state.lock().await;
+    Some(run.events.subscribe())
+}
+
+pub async fn get_pareto_front(runs: &RunStore, run_id: &str) -> Option<(u32, Vec<ParetoPoint>)> {
+    let entry = runs.get(run_id).await?;
+    let mut run = entry.state.lock().await;
+    Some((run.generation, run.pareto_front()))
+}
+
+/// Up to `limit` history points starting at index `offset`, with the total recorded.
+pub async fn get_history(
+    runs: &RunStore,
+    run_id: &str,
+    offset: usize,
+    limit: usize,
+) -> Option<(usize, Vec<RunHistoryPoint>)> {
+    let entry = runs.get(run_id).await?;
+    let run = entry.state.lock().await;
+    let page = run.history.iter().skip(offset).take(limit).cloned().collect();
+    Some((run.history.len(), page))
+}
+
+/// Generation of the last strict increase in best fitness recorded in `history`.
+fn last_improvement(history: &[RunHistoryPoint]) -> u32 {
+    let mut best = f64::NEG_INFINITY;
+    let mut generation = 0;
+    for point in history {
+        if point.best_fitness > best {
+            best = point.best_fitness;
+            generation = point.generation;
+        }
+    }
+    generation
+}
+
+/// Imported ids are used in URLs and as database keys, so they stay short and plain.
+fn is_valid_run_id(id: &str) -> bool {
+    (1..=MAX_RUN_ID_LEN).contains(&id.len())
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+fn generate_run_id(rng: &mut RunRng) -> String {
     let v: u64 = rng.gen();
     format!("{:016x}", v)
 }
@@ -226,3 +1177,671 @@ fn best_index(fitness: &[f64]) -> Option<(usize, f64)> {
         .max_by(|a, b| a.1.partial_cmp(b.1).unwrap_or(std::cmp::Ordering::Equal))
         .map(|(i, &f)| (i, f))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+
+    use super::*;
+    use crate::models::evaluator::{MockEvaluator, ScoreFuture};
+    use crate::models::{
+        CrossoverKind, EvaluatorKind, EvolutionMode, IslandConfig, MigrationTopology,
+        SelectionConfig, StopCriteria,
+    };
+
+    fn cfg(seed: i64) -> RunConfig {
+        RunConfig {
+            seed,
+            population: 20,
+            generations: 10,
+            mutation_rate: 0.5,
+            task: "poly2".to_string(),
+            evaluator: EvaluatorKind::Http,
+            allowed_ops: None,
+            op_weights: None,
+            crossover_rate: 0.0,
+            crossover: CrossoverKind::OnePoint,
+            selection: SelectionConfig::default(),
+            mode: EvolutionMode::Single,
+            islands: None,
+            stop: StopCriteria::default(),
+            seed_genomes: None,
+            replace_duplicates: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn create_and_step_with_mock_evaluator() {
+        let runs = new_store();
+        let evaluators = Evaluators::mock();
+        let run_id = create_run(cfg(7), &runs, &evaluators).await.unwrap();
+        let state = advance_run(&runs, &run_id, 3, &evaluators).await.unwrap();
+        assert_eq!(state.generation, 3);
+        let history = all_history(&runs, &run_id).await;
+        assert_eq!(history.len(), 4);
+        assert!(history
+            .windows(2)
+            .all(|w| w[1].best_fitness >= w[0].best_fitness));
+        assert!(history.iter().all(|p| p.unique_count >= 1 && p.mean_length > 0.0));
+        let (total, page) = get_history(&runs, &run_id, 2, 5).await.unwrap();
+        assert_eq!(total, 4);
+        assert_eq!(page.iter().map(|p| p.generation).collect::<Vec<_>>(), [2, 3]);
+    }
+
+    /// Mock scores that also counts how many genomes reach the evaluator.
+    #[derive(Default)]
+    struct CountingEvaluator(std::sync::atomic::AtomicUsize);
+
+    impl FitnessEvaluator for CountingEvaluator {
+        fn score<'a>(&'a self, task: &'a str, genomes: &'a [Genome]) -> ScoreFuture<'a> {
+            Box::pin(async move {
+                self.0.fetch_add(genomes.len(), Ordering::Relaxed);
+                MockEvaluator.score(task, genomes).await
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn fitness_cache_only_sends_misses_to_the_evaluator() {
+        let counter = Arc::new(CountingEvaluator::default());
+        let evaluators = Evaluators {
+            http: counter.clone(),
+            native: counter.clone(),
+        };
+        let runs = new_store();
+        let run_id = create_run(cfg(9), &runs, &evaluators).await.unwrap();
+        let state = advance_run(&runs, &run_id, 5, &evaluators).await.unwrap();
+        assert!(state.cache_hits > 0);
+        let sent = counter.0.load(Ordering::Relaxed) as u64;
+        assert_eq!(sent, state.evaluations);
+        assert_eq!(state.evaluations + state.cache_hits, 120);
+        assert_eq!(state.cache_hit_rate, state.cache_hits as f64 / 120.0);
+        // The elite was scored a generation earlier, so every step has at least one hit.
+        let history = all_history(&runs, &run_id).await;
+        assert!(history[1..].iter().all(|p| p.cache_hit_rate > 0.0));
+    }
+
+    #[tokio::test]
+    async fn replace_duplicates_keeps_generations_unique() {
+        let evaluators = Evaluators::mock();
+        let mut histories = Vec::new();
+        for replace_duplicates in [false, true] {
+            let mut cfg = cfg(31);
+            cfg.mutation_rate = 0.1;
+            cfg.replace_duplicates = replace_duplicates;
+            let runs = new_store();
+            let run_id = create_run(cfg, &runs, &evaluators).await.unwrap();
+            let state = advance_run(&runs, &run_id, 5, &evaluators).await.unwrap();
+            let history = all_history(&runs, &run_id).await;
+            let replaced: u64 = history.iter().map(|p| u64::from(p.duplicates_replaced)).sum();
+            assert_eq!(state.duplicates_replaced, replaced);
+            assert_eq!(state.unique_count, history[5].unique_count);
+            histories.push(history);
+        }
+        assert!(histories[0].iter().any(|p| p.unique_count < 20));
+        assert!(histories[0].iter().all(|p| p.duplicates_replaced == 0));
+        assert!(histories[1].iter().all(|p| p.unique_count == 20));
+        assert!(histories[1].iter().any(|p| p.duplicates_replaced > 0));
+    }
+
+    #[tokio::test]
+    async fn seeded_runs_are_deterministic() {
+        let evaluators = Evaluators::mock();
+        let mut states = Vec::new();
+        for _ in 0..2 {
+            let runs = new_store();
+            let run_id = create_run(cfg(42), &runs, &evaluators).await.unwrap();
+            let state = advance_run(&runs, &run_id, 5, &evaluators).await.unwrap();
+            states.push((
+                run_id,
+                state.best_fitness,
+                state.best_genome.instructions.len(),
+            ));
+        }
+        assert_eq!(states[0], states[1]);
+    }
+
+    #[tokio::test]
+    async fn restricted_ops_are_honored() {
+        let mut cfg = cfg(3);
+        cfg.allowed_ops = Some(vec![
+            "LOAD".to_string(),
+            "ADD".to_string(),
+            "MUL".to_string(),
+        ]);
+        cfg.op_weights = Some(HashMap::from([("MUL".to_string(), 0.0)]));
+        let runs = new_store();
+        let evaluators = Evaluators::mock();
+        let run_id = create_run(cfg, &runs, &evaluators).await.unwrap();
+        advance_run(&runs, &run_id, 5, &evaluators).await.unwrap();
+        let entry = runs.get(&run_id).await.unwrap();
+        let run = entry.state.lock().await;
+        let ops: Vec<&str> = run
+            .population
+            .iter()
+            .flat_map(|g| g.instructions.iter().map(|i| i.op.as_str()))
+            .collect();
+        assert!(!ops.is_empty());
+        assert!(ops.iter().all(|op| *op == "LOAD" || *op == "ADD"));
+    }
+
+    #[tokio::test]
+    async fn crossover_runs_for_every_kind() {
+        let evaluators = Evaluators::mock();
+        for kind in [
+            CrossoverKind::OnePoint,
+            CrossoverKind::TwoPoint,
+            CrossoverKind::Uniform,
+            CrossoverKind::Homologous,
+        ] {
+            let mut cfg = cfg(11);
+            cfg.crossover_rate = 0.9;
+            cfg.crossover = kind;
+            let runs = new_store();
+            let run_id = create_run(cfg, &runs, &evaluators).await.unwrap();
+            let state = advance_run(&runs, &run_id, 4, &evaluators).await.unwrap();
+            assert_eq!(state.generation, 4);
+            let entry = runs.get(&run_id).await.unwrap();
+            assert_eq!(entry.state.lock().await.population.len(), 20);
+        }
+    }
+
+    #[tokio::test]
+    async fn runs_with_every_selection_method() {
+        let evaluators = Evaluators::mock();
+        for selection in [
+            SelectionConfig::Tournament { k: 5 },
+            SelectionConfig::Roulette,
+            SelectionConfig::Rank,
+            SelectionConfig::Truncation { fraction: 0.25 },
+            SelectionConfig::Sus,
+            SelectionConfig::Lexicase,
+            SelectionConfig::EpsilonLexicase { epsilon: None },
+            SelectionConfig::EpsilonLexicase { epsilon: Some(0.5) },
+        ] {
+            let mut cfg = cfg(13);
+            cfg.crossover_rate = 0.5;
+            cfg.selection = selection;
+            let runs = new_store();
+            let run_id = create_run(cfg, &runs, &evaluators).await.unwrap();
+            let state = advance_run(&runs, &run_id, 3, &evaluators).await.unwrap();
+            assert_eq!(state.generation, 3);
+        }
+    }
+
+    #[tokio::test]
+    async fn nsga2_keeps_population_and_reports_front() {
+        let mut cfg = cfg(21);
+        cfg.mode = EvolutionMode::Nsga2;
+        cfg.crossover_rate = 0.3;
+        let runs = new_store();
+        let evaluators = Evaluators::mock();
+        let run_id = create_run(cfg, &runs, &evaluators).await.unwrap();
+        advance_run(&runs, &run_id, 5, &evaluators).await.unwrap();
+        let (generation, front) = get_pareto_front(&runs, &run_id).await.unwrap();
+        assert_eq!(generation, 5);
+        assert!(!front.is_empty());
+        assert!(front.windows(2).all(|w| w[0].error <= w[1].error));
+        let entry = runs.get(&run_id).await.unwrap();
+        let run = entry.state.lock().await;
+        assert_eq!(run.population.len(), 20);
+        assert_eq!(run.ranks.len(), 20);
+        // The mock rewards short genomes, so the front collapses onto the shortest ones.
+        let shortest = run.population.iter().map(|g| g.instructions.len()).min();
+        assert_eq!(front[0].length as usize, shortest.unwrap());
+    }
+
+    #[tokio::test]
+    async fn island_runs_report_per_island_best() {
+        let evaluators = Evaluators::mock();
+        for topology in [MigrationTopology::Ring, MigrationTopology::Random] {
+            let mut cfg = cfg(17);
+            cfg.population = 21;
+            cfg.islands = Some(IslandConfig {
+                count: 4,
+                migration_interval: 2,
+                migrants: 2,
+                topology,
+            });
+            let runs = new_store();
+            let run_id = create_run(cfg, &runs, &evaluators).await.unwrap();
+            let state = advance_run(&runs, &run_id, 6, &evaluators).await.unwrap();
+            let island_best = state.island_best_fitness.unwrap();
+            assert_eq!(island_best.len(), 4);
+            let overall = island_best.iter().copied().fold(f64::MIN, f64::max);
+            assert_eq!(overall, state.best_fitness);
+            let entry = runs.get(&run_id).await.unwrap();
+            assert_eq!(entry.state.lock().await.population.len(), 21);
+        }
+    }
+
+    #[test]
+    fn migration_copies_best_over_worst_on_ring() {
+        let mut cfg = cfg(1);
+        cfg.population = 6;
+        cfg.islands = Some(IslandConfig {
+            count: 2,
+            migration_interval: 1,
+            migrants: 1,
+            topology: MigrationTopology::Ring,
+        });
+        let mut run = RunInternal::new(cfg).unwrap();
+        run.generation = 1;
+        run.fitness = vec![0.9, 0.1, 0.5, 0.2, 0.3, 0.8];
+        run.steps = vec![0.0; 6];
+        let (best_a, best_b) = (run.population[0].clone(), run.population[5].clone());
+        run.migrate();
+        assert_eq!(run.fitness, vec![0.9, 0.8, 0.5, 0.9, 0.3, 0.8]);
+        assert_eq!(run.population[1].instructions.len(), best_b.instructions.len());
+        assert_eq!(run.population[3].instructions.len(), best_a.instructions.len());
+    }
+
+    #[tokio::test]
+    async fn reloaded_runs_continue_identically() {
+        let evaluators = Evaluators::mock();
+        for mode in [EvolutionMode::Single, EvolutionMode::Nsga2] {
+            let mut cfg = cfg(23);
+            cfg.mode = mode;
+            cfg.crossover_rate = 0.5;
+            let db = Arc::new(RunDb::open_in_memory().unwrap());
+            let runs = open_store(db.clone()).unwrap();
+            let run_id = create_run(cfg, &runs, &evaluators).await.unwrap();
+            advance_run(&runs, &run_id, 3, &evaluators).await.unwrap();
+            runs.flush().await;
+
+            let reloaded = open_store(db.clone()).unwrap();
+            let state = get_run_state(&reloaded, &run_id).await.unwrap();
+            assert_eq!(state.generation, 3);
+            assert_eq!(all_history(&reloaded, &run_id).await.len(), 4);
+            let a = advance_run(&runs, &run_id, 4, &evaluators).await.unwrap();
+            let b = advance_run(&reloaded, &run_id, 4, &evaluators).await.unwrap();
+            assert_eq!(a.best_fitness, b.best_fitness);
+            assert_eq!(
+                evolved_state(&runs, &run_id).await,
+                evolved_state(&reloaded, &run_id).await
+            );
+            // History rows are appended per generation rather than rewritten.
+            runs.flush().await;
+            reloaded.flush().await;
+            let again = open_store(db).unwrap();
+            let history = all_history(&again, &run_id).await;
+            assert_eq!(history.len(), 8);
+            assert!(history.iter().enumerate().all(|(g, p)| p.generation as usize == g));
+        }
+    }
+
+    #[tokio::test]
+    async fn imported_snapshot_continues_identically() {
+        let evaluators = Evaluators::mock();
+        let mut cfg = cfg(29);
+        cfg.population = 12;
+        cfg.crossover_rate = 0.5;
+        cfg.islands = Some(IslandConfig {
+            count: 3,
+            migration_interval: 2,
+            migrants: 1,
+            topology: MigrationTopology::Random,
+        });
+        let source = new_store();
+        let run_id = create_run(cfg, &source, &evaluators).await.unwrap();
+        advance_run(&source, &run_id, 3, &evaluators).await.unwrap();
+
+        let json = serde_json::to_string(&get_snapshot(&source, &run_id).await.unwrap()).unwrap();
+        let target = new_store();
+        let imported = import_run(serde_json::from_str(&json).unwrap(), &target)
+            .await
+            .unwrap();
+        assert_eq!(imported, run_id);
+        let again = import_run(serde_json::from_str(&json).unwrap(), &target).await;
+        assert!(matches!(again, Err(EngineError::BadRequest(_))));
+
+        advance_run(&source, &run_id, 5, &evaluators).await.unwrap();
+        advance_run(&target, &run_id, 5, &evaluators).await.unwrap();
+        assert_eq!(
+            evolved_state(&source, &run_id).await,
+            evolved_state(&target, &run_id).await
+        );
+    }
+
+    #[tokio::test]
+    async fn import_rejects_bad_ids_history_and_genomes() {
+        let evaluators = Evaluators::mock();
+        let source = new_store();
+        let run_id = create_run(cfg(31), &source, &evaluators).await.unwrap();
+        let snapshot = get_snapshot(&source, &run_id).await.unwrap();
+        let target = new_store();
+
+        for id in ["", "../runs", "a/b", "run id", &"x".repeat(65)] {
+            let bad = RunSnapshot {
+                run_id: Some(id.to_string()),
+                ..snapshot.clone()
+            };
+            let result = import_run(bad, &target).await;
+            assert!(matches!(result, Err(EngineError::BadRequest(_))), "{id:?}");
+        }
+        let mut ahead = snapshot.clone();
+        ahead.history[0].generation = ahead.generation + 1;
+        let result = import_run(ahead, &target).await;
+        assert!(matches!(result, Err(EngineError::BadRequest(_))));
+        let mut empty = snapshot.clone();
+        empty.population[0].instructions.clear();
+        let mut long = snapshot.clone();
+        long.best_genome.instructions = vec![long.best_genome.instructions[0].clone(); 65];
+        for bad in [empty, long] {
+            let result = import_run(bad, &target).await;
+            assert!(matches!(result, Err(EngineError::BadRequest(_))));
+        }
+        assert!(target.entries().await.is_empty());
+        assert_eq!(import_run(snapshot, &target).await.unwrap(), run_id);
+    }
+
+    async fn all_history(runs: &RunStore, run_id: &str) -> Vec<RunHistoryPoint> {
+        get_history(runs, run_id, 0, usize::MAX).await.unwrap().1
+    }
+
+    /// Snapshot JSON without the timing and cache counters, which differ between otherwise
+    /// identical runs: a restored run's cache starts out with only its current population.
+    async fn evolved_state(runs: &RunStore, run_id: &str) -> String {
+        let mut snapshot = get_snapshot(runs, run_id).await.unwrap();
+        snapshot.elapsed_secs = 0.0;
+        snapshot.cache_hits = 0;
+        for point in &mut snapshot.history {
+            point.cache_hit_rate = 0.0;
+        }
+        serde_json::to_string(&snapshot).unwrap()
+    }
+
+    async fn wait_for(runs: &RunStore, run_id: &str, done: impl Fn(&RunState) -> bool) -> RunState {
+        for _ in 0..1000 {
+            let state = get_run_state(runs, run_id).await.unwrap();
+            if done(&state) {
+                return state;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        }
+        panic!("run {} never reached the expected state", run_id);
+    }
+
+    /// Waits for the step in flight on the run, if any, to finish. Steps hold the run's
+    /// `stepping` lock throughout, and any that start later see the current status.
+    async fn settle(runs: &RunStore, run_id: &str) {
+        let entry = runs.entry(run_id).await.unwrap();
+        drop(entry.stepping.lock().await);
+    }
+
+    #[tokio::test]
+    async fn native_runs_match_across_thread_counts() {
+        let mut cfg = cfg(17);
+        cfg.evaluator = EvaluatorKind::Native;
+        cfg.population = 200;
+        let mut states = Vec::new();
+        for threads in [1, 4] {
+            let evaluators = Evaluators::new("http://127.0.0.1:0", threads).unwrap();
+            let runs = new_store();
+            let run_id = create_run(cfg.clone(), &runs, &evaluators).await.unwrap();
+            advance_run(&runs, &run_id, 5, &evaluators).await.unwrap();
+            states.push(evolved_state(&runs, &run_id).await);
+        }
+        assert_eq!(states[0], states[1]);
+    }
+
+    #[tokio::test]
+    async fn advance_stops_at_generation_limit() {
+        let runs = new_store();
+        let evaluators = Evaluators::mock();
+        let run_id = create_run(cfg(5), &runs, &evaluators).await.unwrap();
+        let state = advance_run(&runs, &run_id, 15, &evaluators).await.unwrap();
+        assert_eq!(state.generation, 10);
+        assert_eq!(state.status, RunStatus::Finished);
+        assert_eq!(state.stop_reason, Some(StopReason::GenerationLimit));
+        assert_eq!(state.evaluations + state.cache_hits, 20 * 11);
+        let err = step_run(&runs, &run_id, &evaluators).await.unwrap_err();
+        assert!(matches!(err, EngineError::BadRequest(_)));
+    }
+
+    #[tokio::test]
+    async fn stop_criteria_finish_runs_early() {
+        let evaluators = Evaluators::mock();
+        let cases = [
+            (
+                StopCriteria {
+                    target_fitness: Some(0.0),
+                    ..StopCriteria::default()
+                },
+                StopReason::TargetFitness,
+            ),
+            (
+                StopCriteria {
+                    max_evaluations: Some(20 * 4),
+                    ..StopCriteria::default()
+                },
+                StopReason::MaxEvaluations,
+            ),
+            (
+                StopCriteria {
+                    stagnation_generations: Some(2),
+                    ..StopCriteria::default()
+                },
+                StopReason::Stagnation,
+            ),
+            (
+                StopCriteria {
+                    max_wall_time_secs: Some(1e-12),
+                    ..StopCriteria::default()
+                },
+                StopReason::MaxWallTime,
+            ),
+        ];
+        for (stop, reason) in cases {
+            let mut cfg = cfg(41);
+            cfg.generations = 1000;
+            cfg.stop = stop;
+            let runs = new_store();
+            let run_id = create_run(cfg, &runs, &evaluators).await.unwrap();
+            let _ = advance_run(&runs, &run_id, 1000, &evaluators).await;
+            let state = get_run_state(&runs, &run_id).await.unwrap();
+            assert_eq!(state.status, RunStatus::Finished);
+            assert_eq!(state.stop_reason, Some(reason));
+            assert!(state.generation < 1000);
+            match reason {
+                StopReason::TargetFitness | StopReason::MaxWallTime => {
+                    assert_eq!(state.generation, 0)
+                }
+                // Cache hits are free, so the budget lasts past generation 3; a step
+                // sends at most the whole population.
+                StopReason::MaxEvaluations => {
+                    assert!(state.generation > 3);
+                    assert!((20 * 4..20 * 5).contains(&state.evaluations));
+                }
+                _ => {
+                    let history = all_history(&runs, &run_id).await;
+                    let tail = &history[history.len() - 3..];
+                    assert!(tail.iter().all(|p| p.best_fitness == tail[0].best_fitness));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn seed_genomes_replace_leading_individuals() {
+        let plain = RunInternal::new(cfg(8)).unwrap();
+        let mut seeded_cfg = cfg(8);
+        seeded_cfg.seed_genomes = Some(vec!["LOAD 0\nDUP\nMUL".to_string()]);
+        let seeded = RunInternal::new(seeded_cfg).unwrap();
+        assert_eq!(genome::to_assembly(&seeded.population[0]), "LOAD 0\nDUP\nMUL\n");
+        assert_eq!(
+            serde_json::to_string(&seeded.population[1..]).unwrap(),
+            serde_json::to_string(&plain.population[1..]).unwrap()
+        );
+
+        let mut bad = cfg(8);
+        bad.seed_genomes = Some(vec!["LOAD 0".to_string(), "FROB".to_string()]);
+        let err = RunInternal::new(bad).err().unwrap().to_string();
+        assert_eq!(err, "bad request: seed_genomes[1]: line 1: unknown op FROB");
+
+        let mut long = cfg(8);
+        long.seed_genomes = Some(vec!["NOP\n".repeat(65)]);
+        let err = RunInternal::new(long).err().unwrap().to_string();
+        assert!(err.contains("between 1 and 64 instructions"), "{err}");
+        let mut disallowed = cfg(8);
+        disallowed.allowed_ops = Some(vec!["LOAD".to_string(), "MUL".to_string()]);
+        disallowed.seed_genomes = Some(vec!["LOAD 0\nDUP\nMUL".to_string()]);
+        let err = RunInternal::new(disallowed).err().unwrap().to_string();
+        assert_eq!(err, "bad request: seed_genomes[0]: DUP is not an allowed op");
+    }
+
+    #[tokio::test]
+    async fn background_run_finishes_at_generation_limit() {
+        let runs = new_store();
+        let evaluators = Evaluators::mock();
+        let run_id = create_run(cfg(9), &runs, &evaluators).await.unwrap();
+        start_run(&runs, &run_id, &evaluators).await.unwrap();
+        let state = wait_for(&runs, &run_id, |s| s.status == RunStatus::Finished).await;
+        assert_eq!(state.generation, 10);
+        assert_eq!(all_history(&runs, &run_id).await.len(), 11);
+    }
+
+    #[tokio::test]
+    async fn background_run_pauses_resumes_and_cancels() {
+        let mut cfg = cfg(10);
+        cfg.generations = 1_000_000;
+        let runs = new_store();
+        let evaluators = Evaluators::mock();
+        let run_id = create_run(cfg, &runs, &evaluators).await.unwrap();
+        start_run(&runs, &run_id, &evaluators).await.unwrap();
+        let err = step_run(&runs, &run_id, &evaluators).await.unwrap_err();
+        assert!(matches!(err, EngineError::BadRequest(_)));
+
+        wait_for(&runs, &run_id, |s| s.generation > 2).await;
+        // A generation being scored when the pause lands is dropped, not committed.
+        let paused = pause_run(&runs, &run_id).await.unwrap().generation;
+        settle(&runs, &run_id).await;
+        let state = get_run_state(&runs, &run_id).await.unwrap();
+        assert_eq!((state.status, state.generation), (RunStatus::Paused, paused));
+
+        resume_run(&runs, &run_id, &evaluators).await.unwrap();
+        wait_for(&runs, &run_id, |s| s.generation > paused + 2).await;
+        let state = cancel_run(&runs, &run_id).await.unwrap();
+        assert_eq!(state.status, RunStatus::Idle);
+        let stepped = step_run(&runs, &run_id, &evaluators).await.unwrap();
+        assert!(stepped.generation > state.generation);
+        assert!(pause_run(&runs, &run_id).await.is_err());
+    }
+
+    /// Mock scores that, while armed, hold each call until the test releases it.
+    #[derive(Default)]
+    struct GatedEvaluator {
+        armed: std::sync::atomic::AtomicBool,
+        entered: tokio::sync::Notify,
+        release: tokio::sync::Notify,
+    }
+
+    impl FitnessEvaluator for GatedEvaluator {
+        fn score<'a>(&'a self, task: &'a str, genomes: &'a [Genome]) -> ScoreFuture<'a> {
+            Box::pin(async move {
+                if self.armed.load(Ordering::SeqCst) {
+                    self.entered.notify_one();
+                    self.release.notified().await;
+                }
+                MockEvaluator.score(task, genomes).await
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn pause_drops_the_generation_being_scored() {
+        let gate = Arc::new(GatedEvaluator::default());
+        let evaluators = Evaluators {
+            http: gate.clone(),
+            native: gate.clone(),
+        };
+        let mut cfg = cfg(12);
+        cfg.generations = 1_000_000;
+        let runs = new_store();
+        let run_id = create_run(cfg, &runs, &evaluators).await.unwrap();
+        let before = get_snapshot(&runs, &run_id).await.unwrap();
+
+        gate.armed.store(true, Ordering::SeqCst);
+        start_run(&runs, &run_id, &evaluators).await.unwrap();
+        gate.entered.notified().await;
+        let paused = pause_run(&runs, &run_id).await.unwrap();
+        gate.armed.store(false, Ordering::SeqCst);
+        gate.release.notify_one();
+        settle(&runs, &run_id).await;
+
+        let state = get_run_state(&runs, &run_id).await.unwrap();
+        assert_eq!((state.status, state.generation), (RunStatus::Paused, 0));
+        assert_eq!(paused.generation, 0);
+        // The RNG was rewound, so the dropped generation is redrawn identically.
+        let after = get_snapshot(&runs, &run_id).await.unwrap();
+        assert_eq!(
+            serde_json::to_string(&after.rng).unwrap(),
+            serde_json::to_string(&before.rng).unwrap()
+        );
+        resume_run(&runs, &run_id, &evaluators).await.unwrap();
+        wait_for(&runs, &run_id, |s| s.generation > 1).await;
+        cancel_run(&runs, &run_id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn subscribers_receive_every_generation() {
+        let runs = new_store();
+        let evaluators = Evaluators::mock();
+        let run_id = create_run(cfg(31), &runs, &evaluators).await.unwrap();
+        let mut events = subscribe(&runs, &run_id).await.unwrap();
+        advance_run(&runs, &run_id, 2, &evaluators).await.unwrap();
+        for generation in 1..=2 {
+            let event = events.recv().await.unwrap();
+            assert_eq!((event.run_id.as_str(), event.generation), (run_id.as_str(), generation));
+            assert!(event.mean_fitness <= event.best_fitness);
+        }
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_steps_on_one_run_are_linearizable() {
+        let evaluators = Evaluators::mock();
+        let serial = new_store();
+        let run_id = create_run(cfg(37), &serial, &evaluators).await.unwrap();
+        advance_run(&serial, &run_id, 8, &evaluators).await.unwrap();
+
+        let concurrent = new_store();
+        create_run(cfg(37), &concurrent, &evaluators).await.unwrap();
+        let steps: Vec<_> = (0..8)
+            .map(|_| {
+                let (runs, run_id, evaluators) =
+                    (concurrent.clone(), run_id.clone(), evaluators.clone());
+                tokio::spawn(async move { step_run(&runs, &run_id, &evaluators).await })
+            })
+            .collect();
+        let mut generations = Vec::new();
+        for step in steps {
+            generations.push(step.await.unwrap().unwrap().generation);
+        }
+        generations.sort();
+        assert_eq!(generations, (1..=8).collect::<Vec<i64>>());
+        assert_eq!(
+            evolved_state(&serial, &run_id).await,
+            evolved_state(&concurrent, &run_id).await
+        );
+    }
+
+    #[tokio::test]
+    async fn unknown_allowed_op_is_rejected() {
+        let mut cfg = cfg(3);
+        cfg.allowed_ops = Some(vec!["FROB".to_string()]);
+        let err = create_run(cfg, &new_store(), &Evaluators::mock())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, EngineError::BadRequest(_)));
+    }
+
+    #[tokio::test]
+    async fn step_unknown_run_is_not_found() {
+        let runs = new_store();
+        let err = step_run(&runs, "missing", &Evaluators::mock())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, EngineError::NotFound(_)));
+    }
+}
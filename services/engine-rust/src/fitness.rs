@@ -0,0 +1,133 @@
+use rayon::prelude::*;
+use rayon::ThreadPool;
+
+use crate::models::bytecode::Program;
+use crate::models::vm::{Vm, VmConfig, VmOutcome};
+use crate::models::Genome;
+
+const INVALID_FITNESS: f64 = 1e-9;
+
+/// Aggregate fitness plus the absolute error on every test case, one row per genome.
+/// Invalid genomes carry `f64::INFINITY` on every case and `max_steps` as their step count.
+#[derive(Debug, Clone, Default)]
+pub struct Scores {
+    pub fitness: Vec<f64>,
+    pub case_errors: Vec<Vec<f64>>,
+    /// Mean number of executed VM steps per test case.
+    pub steps: Vec<f64>,
+}
+
+impl Scores {
+    pub fn extend(&mut self, other: Scores) {
+        self.fitness.extend(other.fitness);
+        self.case_errors.extend(other.case_errors);
+        self.steps.extend(other.steps);
+    }
+
+    /// Keeps only the rows at `indices`, in that order.
+    pub fn select(&self, indices: &[usize]) -> Scores {
+        let pick = |v: &[f64], i: usize| v.get(i).copied().unwrap_or(0.0);
+        Scores {
+            fitness: indices.iter().map(|&i| pick(&self.fitness, i)).collect(),
+            case_errors: indices
+                .iter()
+                .map(|&i| self.case_errors.get(i).cloned().unwrap_or_default())
+                .collect(),
+            steps: indices.iter().map(|&i| pick(&self.steps, i)).collect(),
+        }
+    }
+}
+
+/// Test cases for `task`. poly2 is the only task so far; unknown tasks fall back to it
+/// to keep behavior deterministic.
+pub fn get_cases(_task: &str) -> Vec<(f64, f64)> {
+    (-5..=5).map(|x| (x as f64, poly2(x as f64))).collect()
+}
+
+pub fn poly2(x: f64) -> f64 {
+    x * x + 3.0 * x + 2.0
+}
+
+pub fn is_invalid_fitness(fitness: f64) -> bool {
+    fitness <= INVALID_FITNESS
+}
+
+pub fn score_genomes(task: &str, genomes: &[Genome]) -> Scores {
+    let cases = get_cases(task);
+    collect_scores(genomes.iter().map(|genome| score_genome(genome, &cases)))
+}
+
+/// Scores genomes across `pool`. Each genome is scored independently and results keep the
+/// input order, so this matches [`score_genomes`] exactly.
+pub fn score_genomes_parallel(task: &str, genomes: &[Genome], pool: &ThreadPool) -> Scores {
+    let cases = get_cases(task);
+    let rows: Vec<(f64, Vec<f64>, f64)> = pool.install(|| {
+        genomes
+            .par_iter()
+            .map(|genome| score_genome(genome, &cases))
+            .collect()
+    });
+    collect_scores(rows.into_iter())
+}
+
+fn collect_scores(rows: impl Iterator<Item = (f64, Vec<f64>, f64)>) -> Scores {
+    let mut scores = Scores::default();
+    for (fitness, errors, steps) in rows {
+        scores.fitness.push(fitness);
+        scores.case_errors.push(errors);
+        scores.steps.push(steps);
+    }
+    scores
+}
+
+pub fn score_genome(genome: &Genome, cases: &[(f64, f64)]) -> (f64, Vec<f64>, f64) {
+    let mut program = Program::lazy(genome);
+    let mut vm = Vm::default();
+    let mut errors = Vec::with_capacity(cases.len());
+    let mut total_steps = 0;
+    for &(x, expected) in cases {
+        match vm.run(&mut program, x, VmConfig::default()) {
+            VmOutcome::Ok { output, steps } => {
+                errors.push((output - expected).abs());
+                total_steps += steps;
+            }
+            VmOutcome::Invalid { reason } => {
+                tracing::trace!("genome invalid on x={}: {}", x, reason);
+                return (
+                    INVALID_FITNESS,
+                    vec![f64::INFINITY; cases.len()],
+                    VmConfig::default().max_steps as f64,
+                );
+            }
+        }
+    }
+    if cases.is_empty() {
+        return (0.0, errors, 0.0);
+    }
+    let n = cases.len() as f64;
+    let mean_error = errors.iter().sum::<f64>() / n;
+    (1.0 / (1.0 + mean_error), errors, total_steps as f64 / n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::genome::{random_genome, OpSet};
+    use crate::models::rng::seeded_rng;
+
+    #[test]
+    fn parallel_scoring_matches_serial() {
+        let mut rng = seeded_rng(12);
+        let genomes: Vec<Genome> = (0..500)
+            .map(|_| random_genome(&mut rng, &OpSet::all()))
+            .collect();
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(4)
+            .build()
+            .unwrap();
+        assert_eq!(
+            format!("{:?}", score_genomes_parallel("poly2", &genomes, &pool)),
+            format!("{:?}", score_genomes("poly2", &genomes))
+        );
+    }
+}
@@ -1,27 +1,174 @@
-use rand::{rngs::StdRng, Rng};
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
 
-use crate::models::rng::{gen_range_f64, gen_range_usize};
-use crate::models::{Genome, Instruction};
+use rand::Rng;
+
+use crate::models::bytecode::{Op, Program};
+use crate::models::rng::{gen_range_f64, gen_range_usize, RunRng};
+use crate::models::{CrossoverKind, Genome, Instruction};
 
 pub const REGISTER_COUNT: usize = 4;
-const OPS: [&str; 12] = [
+pub const MAX_JUMP: i64 = 4;
+const OPS: [&str; 25] = [
     "PUSH", "LOAD", "STORE", "ADD", "SUB", "MUL", "DIV", "DUP", "SWAP", "POP", "HALT", "NOP",
+    "JMP", "JZ", "JNZ", "LT", "GT", "EQ", "SIN", "COS", "EXP", "LOG", "SQRT", "ABS", "NEG",
 ];
 const MIN_LEN: usize = 8;
 const MAX_LEN: usize = 32;
-const ABS_MAX_LEN: usize = 64;
+/// Longest genome the engine evolves, evaluates or accepts from a client.
+pub const ABS_MAX_LEN: usize = 64;
+/// Random genomes drawn per duplicate before a repeat is accepted.
+const REDRAW_ATTEMPTS: usize = 8;
 
-pub fn parse_register_index(arg: Option<f64>) -> Result<usize, String> {
+pub fn parse_register_index(arg: Option<f64>) -> Option<usize> {
     match arg {
         Some(val) if val == 0.0 || val == 1.0 || val == 2.0 || val == 3.0 => {
-            Ok(val as usize)
+            Some(val as usize)
+        }
+        _ => None,
+    }
+}
+
+/// Jump args are relative offsets from the jump itself, so they must be whole numbers.
+pub fn parse_jump_offset(arg: Option<f64>) -> Option<isize> {
+    match arg {
+        Some(val) if val.is_finite() && val.fract() == 0.0 && val.abs() <= 1e9 => {
+            Some(val as isize)
         }
-        _ => Err("invalid register index".to_string()),
+        _ => None,
+    }
+}
+
+/// Args compare by their bits, so `0.0` and `-0.0` differ and a NaN equals itself. That
+/// keeps `Eq` lawful and only merges genomes the VM cannot tell apart.
+impl PartialEq for Instruction {
+    fn eq(&self, other: &Self) -> bool {
+        self.op == other.op && self.arg.map(f64::to_bits) == other.arg.map(f64::to_bits)
+    }
+}
+
+impl Eq for Instruction {}
+
+impl Hash for Instruction {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.op.hash(state);
+        self.arg.map(f64::to_bits).hash(state);
     }
 }
 
-pub fn random_instruction(rng: &mut StdRng) -> Instruction {
-    let op = OPS[gen_range_usize(rng, OPS.len())];
+/// The opcodes a run may generate, with optional sampling weights.
+#[derive(Debug, Clone)]
+pub struct OpSet {
+    ops: Vec<&'static str>,
+    cumulative_weights: Option<Vec<f64>>,
+}
+
+impl OpSet {
+    pub fn all() -> Self {
+        Self {
+            ops: OPS.to_vec(),
+            cumulative_weights: None,
+        }
+    }
+
+    /// Ops missing from `weights` default to weight 1.0.
+    pub fn from_config(
+        allowed: Option<&[String]>,
+        weights: Option<&HashMap<String, f64>>,
+    ) -> Result<Self, String> {
+        let ops = match allowed {
+            Some(names) => {
+                let mut ops: Vec<&'static str> = Vec::with_capacity(names.len());
+                for name in names {
+                    let op = lookup_op(name)?;
+                    if !ops.contains(&op) {
+                        ops.push(op);
+                    }
+                }
+                if ops.is_empty() {
+                    return Err("allowed_ops must be non-empty".to_string());
+                }
+                ops
+            }
+            None => OPS.to_vec(),
+        };
+
+        let cumulative_weights = match weights {
+            Some(weights) => {
+                for (name, w) in weights {
+                    let op = lookup_op(name)?;
+                    if !ops.contains(&op) {
+                        return Err(format!("op_weights entry {name} is not an allowed op"));
+                    }
+                    if !w.is_finite() || *w < 0.0 {
+                        return Err(format!("op_weights entry {name} must be non-negative"));
+                    }
+                }
+                let mut total = 0.0;
+                let cumulative: Vec<f64> = ops
+                    .iter()
+                    .map(|op| {
+                        total += weights.get(*op).copied().unwrap_or(1.0);
+                        total
+                    })
+                    .collect();
+                if total <= 0.0 {
+                    return Err("op_weights must not all be zero".to_string());
+                }
+                Some(cumulative)
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            ops,
+            cumulative_weights,
+        })
+    }
+
+    pub fn contains(&self, op: &str) -> bool {
+        self.ops.contains(&op)
+    }
+
+    fn sample(&self, rng: &mut RunRng) -> &'static str {
+        match &self.cumulative_weights {
+            Some(cumulative) => {
+                let total = cumulative[cumulative.len() - 1];
+                let r = rng.gen::<f64>() * total;
+                let idx = cumulative.partition_point(|&c| c <= r);
+                self.ops[idx.min(self.ops.len() - 1)]
+            }
+            None => self.ops[gen_range_usize(rng, self.ops.len())],
+        }
+    }
+}
+
+impl Default for OpSet {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+fn lookup_op(name: &str) -> Result<&'static str, String> {
+    OPS.iter()
+        .copied()
+        .find(|op| *op == name)
+        .ok_or_else(|| format!("unknown op {name}"))
+}
+
+/// Checks that `genome` has at least one and at most [`ABS_MAX_LEN`] instructions.
+pub fn check_length(genome: &Genome) -> Result<(), String> {
+    let len = genome.instructions.len();
+    if len == 0 || len > ABS_MAX_LEN {
+        return Err(format!(
+            "genome must have between 1 and {ABS_MAX_LEN} instructions, got {len}"
+        ));
+    }
+    Ok(())
+}
+
+pub fn random_instruction(rng: &mut RunRng, ops: &OpSet) -> Instruction {
+    let op = ops.sample(rng);
     match op {
         "PUSH" => Instruction {
             op: op.to_string(),
@@ -31,6 +178,14 @@ pub fn random_instruction(rng: &mut StdRng) -> Instruction {
             op: op.to_string(),
             arg: Some(gen_range_usize(rng, REGISTER_COUNT) as f64),
         },
+        "JMP" | "JZ" | "JNZ" => {
+            let magnitude = rng.gen_range(1..=MAX_JUMP);
+            let offset = if rng.gen::<bool>() { magnitude } else { -magnitude };
+            Instruction {
+                op: op.to_string(),
+                arg: Some(offset as f64),
+            }
+        }
         _ => Instruction {
             op: op.to_string(),
             arg: None,
@@ -38,41 +193,62 @@ pub fn random_instruction(rng: &mut StdRng) -> Instruction {
     }
 }
 
-pub fn random_genome(rng: &mut StdRng) -> Genome {
+pub fn random_genome(rng: &mut RunRng, ops: &OpSet) -> Genome {
     let len = rng.gen_range(MIN_LEN..=MAX_LEN);
     Genome {
-        instructions: (0..len).map(|_| random_instruction(rng)).collect(),
+        instructions: (0..len).map(|_| random_instruction(rng, ops)).collect(),
+    }
+}
+
+/// Replaces every repeat of an earlier genome with a random one, redrawing a few times if
+/// the random genome is itself a repeat. Returns how many genomes were replaced.
+pub fn replace_duplicates(population: &mut [Genome], rng: &mut RunRng, ops: &OpSet) -> usize {
+    let mut seen = HashSet::with_capacity(population.len());
+    let mut replaced = 0;
+    for genome in population.iter_mut() {
+        if seen.contains(genome) {
+            *genome = random_genome(rng, ops);
+            for _ in 1..REDRAW_ATTEMPTS {
+                if !seen.contains(genome) {
+                    break;
+                }
+                *genome = random_genome(rng, ops);
+            }
+            replaced += 1;
+        }
+        seen.insert(genome.clone());
     }
+    replaced
 }
 
-pub fn mutate_genome(genome: &mut Genome, rng: &mut StdRng) {
+pub fn mutate_genome(genome: &mut Genome, rng: &mut RunRng, ops: &OpSet) {
     if genome.instructions.is_empty() {
-        genome.instructions.push(random_instruction(rng));
+        genome.instructions.push(random_instruction(rng, ops));
         return;
     }
 
     let choice = rng.gen_range(0..4);
     match choice {
-        0 => point_mutate(genome, rng),
-        1 => tweak_push(genome, rng),
-        2 => insert_instruction(genome, rng),
+        0 => point_mutate(genome, rng, ops),
+        1 => tweak_push(genome, rng, ops),
+        2 => insert_instruction(genome, rng, ops),
         _ => delete_instruction(genome, rng),
     }
 
     if genome.instructions.is_empty() {
-        genome.instructions.push(random_instruction(rng));
+        genome.instructions.push(random_instruction(rng, ops));
     }
     if genome.instructions.len() > ABS_MAX_LEN {
         genome.instructions.truncate(ABS_MAX_LEN);
     }
 }
 
-fn point_mutate(genome: &mut Genome, rng: &mut StdRng) {
+fn point_mutate(genome: &mut Genome, rng: &mut RunRng, ops: &OpSet) {
     let idx = gen_range_usize(rng, genome.instructions.len());
-    genome.instructions[idx] = random_instruction(rng);
+    genome.instructions[idx] = random_instruction(rng, ops);
 }
 
-fn tweak_push(genome: &mut Genome, rng: &mut StdRng) {
+fn tweak_push(genome: &mut Genome, rng: &mut RunRng, ops: &OpSet) {
     let push_indices: Vec<usize> = genome
         .instructions
         .iter()
@@ -80,7 +256,7 @@ fn tweak_push(genome: &mut Genome, rng: &mut StdRng) {
         .filter_map(|(i, instr)| (instr.op == "PUSH").then_some(i))
         .collect();
     if push_indices.is_empty() {
-        point_mutate(genome, rng);
+        point_mutate(genome, rng, ops);
         return;
     }
     let idx = push_indices[rng.gen_range(0..push_indices.len())];
@@ -89,19 +265,747 @@ fn tweak_push(genome: &mut Genome, rng: &mut StdRng) {
     genome.instructions[idx].arg = Some(new_val.clamp(-10.0, 10.0));
 }
 
-fn insert_instruction(genome: &mut Genome, rng: &mut StdRng) {
+fn insert_instruction(genome: &mut Genome, rng: &mut RunRng, ops: &OpSet) {
     if genome.instructions.len() >= ABS_MAX_LEN {
-        point_mutate(genome, rng);
+        point_mutate(genome, rng, ops);
         return;
     }
     let idx = rng.gen_range(0..=genome.instructions.len());
-    genome.instructions.insert(idx, random_instruction(rng));
+    genome.instructions.insert(idx, random_instruction(rng, ops));
 }
 
-fn delete_instruction(genome: &mut Genome, rng: &mut StdRng) {
+fn delete_instruction(genome: &mut Genome, rng: &mut RunRng) {
     if genome.instructions.len() <= 1 {
         return;
     }
     let idx = gen_range_usize(rng, genome.instructions.len());
     genome.instructions.remove(idx);
 }
+
+pub fn crossover(a: &Genome, b: &Genome, kind: CrossoverKind, rng: &mut RunRng) -> Genome {
+    let (a, b) = (&a.instructions, &b.instructions);
+    let mut instructions = match kind {
+        CrossoverKind::OnePoint => {
+            let cut_a = rng.gen_range(0..=a.len());
+            let cut_b = rng.gen_range(0..=b.len());
+            [&a[..cut_a], &b[cut_b..]].concat()
+        }
+        CrossoverKind::TwoPoint => {
+            let (a1, a2) = ordered_cuts(a.len(), rng);
+            let (b1, b2) = ordered_cuts(b.len(), rng);
+            [&a[..a1], &b[b1..b2], &a[a2..]].concat()
+        }
+        CrossoverKind::Uniform => a
+            .iter()
+            .enumerate()
+            .map(|(i, instr)| match b.get(i) {
+                Some(other) if rng.gen::<bool>() => other.clone(),
+                _ => instr.clone(),
+            })
+            .collect(),
+        CrossoverKind::Homologous => {
+            // Same cut in both parents so instructions keep their positions.
+            let cut = rng.gen_range(0..=a.len().min(b.len()));
+            [&a[..cut], &b[cut..]].concat()
+        }
+    };
+
+    instructions.truncate(ABS_MAX_LEN);
+    if instructions.is_empty() {
+        instructions.push(a.first().or(b.first()).cloned().unwrap_or(Instruction {
+            op: "NOP".to_string(),
+            arg: None,
+        }));
+    }
+    Genome { instructions }
+}
+
+fn ordered_cuts(len: usize, rng: &mut RunRng) -> (usize, usize) {
+    let x = rng.gen_range(0..=len);
+    let y = rng.gen_range(0..=len);
+    (x.min(y), x.max(y))
+}
+
+/// Prints a genome in the assembly syntax read by [`parse_assembly`], one instruction per
+/// line. Jumps that land inside the genome (or just past its end) target generated labels
+/// `L0`, `L1`, ...; other args are printed as numbers. A genome whose ops and args all
+/// pass the parser's checks, as generated and mutated ones do, parses back to the same
+/// bits. Unknown ops and out-of-range args, which JSON input can still carry, are printed
+/// as they are, and the parser rejects them.
+pub fn to_assembly(genome: &Genome) -> String {
+    let len = genome.instructions.len();
+    let mut targets: Vec<usize> = genome
+        .instructions
+        .iter()
+        .enumerate()
+        .filter_map(|(pc, instr)| jump_target(pc, instr, len))
+        .collect();
+    targets.sort_unstable();
+    targets.dedup();
+    let label = |target: usize| targets.binary_search(&target).ok().map(|i| format!("L{i}"));
+
+    let mut out = String::new();
+    for (pc, instr) in genome.instructions.iter().enumerate() {
+        if let Some(name) = label(pc) {
+            out.push_str(&format!("{name}:\n"));
+        }
+        out.push_str(&instr.op);
+        match (jump_target(pc, instr, len).and_then(label), instr.arg) {
+            (Some(name), _) => out.push_str(&format!(" {name}")),
+            // Debug keeps the ".0" on whole constants so `PUSH 2.0` reads as a value.
+            (None, Some(arg)) if instr.op == "PUSH" => out.push_str(&format!(" {arg:?}")),
+            (None, Some(arg)) => out.push_str(&format!(" {arg}")),
+            (None, None) => {}
+        }
+        out.push('\n');
+    }
+    if let Some(name) = label(len) {
+        out.push_str(&format!("{name}:\n"));
+    }
+    out
+}
+
+/// Absolute target of a jump whose offset stays within `0..=len` and prints back exactly.
+fn jump_target(pc: usize, instr: &Instruction, len: usize) -> Option<usize> {
+    if !matches!(instr.op.as_str(), "JMP" | "JZ" | "JNZ") {
+        return None;
+    }
+    let offset = parse_jump_offset(instr.arg)?;
+    let target = pc.checked_add_signed(offset).filter(|&t| t <= len)?;
+    // -0.0 is a valid offset but would come back as +0.0 through a label.
+    (instr.arg?.to_bits() == (offset as f64).to_bits()).then_some(target)
+}
+
+/// Parses the genome assembly syntax:
+///
+/// ```text
+/// ; squares r0
+/// start:  LOAD 0      # comments run from `;` or `#` to the end of the line
+///         DUP
+///         MUL
+///         JMP end     ; jumps take a label or a relative offset such as -2
+/// end:
+/// ```
+///
+/// Op names are case-insensitive, labels are identifiers followed by `:`, and numeric
+/// line prefixes such as `0:` are accepted and ignored. `PUSH` takes a finite number,
+/// `LOAD`/`STORE` a register and jumps a label or whole offset; other ops take nothing.
+pub fn parse_assembly(text: &str) -> Result<Genome, String> {
+    let mut instructions = Vec::new();
+    let mut labels: HashMap<&str, usize> = HashMap::new();
+    let mut pending: Vec<(usize, usize, &str)> = Vec::new();
+
+    for (line_no, line) in text.lines().enumerate() {
+        let line_no = line_no + 1;
+        let code = line.split([';', '#']).next().unwrap_or("");
+        let mut tokens = code.split_whitespace().peekable();
+        while let Some(name) = tokens.peek().and_then(|t| t.strip_suffix(':')) {
+            tokens.next();
+            if name.parse::<usize>().is_ok() {
+                continue;
+            }
+            if !is_label(name) {
+                return Err(format!("line {line_no}: invalid label {name}"));
+            }
+            if labels.insert(name, instructions.len()).is_some() {
+                return Err(format!("line {line_no}: duplicate label {name}"));
+            }
+        }
+        let Some(op) = tokens.next() else {
+            continue;
+        };
+        let op = lookup_op(&op.to_ascii_uppercase()).map_err(|e| format!("line {line_no}: {e}"))?;
+        let arg = match tokens.next() {
+            None => None,
+            Some(token) => match token.parse::<f64>() {
+                Ok(value) => Some(value),
+                Err(_) if matches!(op, "JMP" | "JZ" | "JNZ") && is_label(token) => {
+                    pending.push((line_no, instructions.len(), token));
+                    Some(0.0)
+                }
+                Err(_) => return Err(format!("line {line_no}: invalid argument {token}")),
+            },
+        };
+        if let Some(extra) = tokens.next() {
+            return Err(format!("line {line_no}: unexpected {extra}"));
+        }
+        check_arg(op, arg).map_err(|e| format!("line {line_no}: {e}"))?;
+        instructions.push(Instruction {
+            op: op.to_string(),
+            arg,
+        });
+    }
+
+    for (line_no, pc, name) in pending {
+        let target = *labels
+            .get(name)
+            .ok_or_else(|| format!("line {line_no}: undefined label {name}"))?;
+        instructions[pc].arg = Some(target as f64 - pc as f64);
+    }
+    if instructions.is_empty() {
+        return Err("genome has no instructions".to_string());
+    }
+    Ok(Genome { instructions })
+}
+
+/// Arity and range of an instruction's argument, as the VM and the mutation operators
+/// produce them.
+fn check_arg(op: &str, arg: Option<f64>) -> Result<(), String> {
+    match (op, arg) {
+        ("PUSH" | "LOAD" | "STORE" | "JMP" | "JZ" | "JNZ", None) => {
+            Err(format!("{op} needs an argument"))
+        }
+        (_, None) => Ok(()),
+        ("PUSH", Some(value)) if !value.is_finite() => Err("PUSH needs a finite value".to_string()),
+        ("LOAD" | "STORE", arg) if parse_register_index(arg).is_none() => {
+            Err(format!("{op} needs a register from 0 to {}", REGISTER_COUNT - 1))
+        }
+        ("JMP" | "JZ" | "JNZ", arg) if parse_jump_offset(arg).is_none() => {
+            Err(format!("{op} needs a label or a whole offset"))
+        }
+        ("PUSH" | "LOAD" | "STORE" | "JMP" | "JZ" | "JNZ", Some(_)) => Ok(()),
+        (_, Some(_)) => Err(format!("{op} takes no argument")),
+    }
+}
+
+/// Identifiers other than those that parse as floats, so a jump to one is never read as an
+/// offset.
+fn is_label(name: &str) -> bool {
+    if ["inf", "infinity", "nan"]
+        .iter()
+        .any(|float| name.eq_ignore_ascii_case(float))
+    {
+        return false;
+    }
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Marks each instruction as effective (`true`) or an intron that [`simplify`] drops:
+/// unreachable code such as anything after an unconditional `HALT`, `NOP`s, a final
+/// `HALT`, jumps to the next instruction, and values pushed only to be popped again,
+/// including those kept by a `STORE` to a register that is never loaded afterwards.
+pub fn effective_instructions(genome: &Genome) -> Vec<bool> {
+    let mut effective = vec![false; genome.instructions.len()];
+    for node in reduce(genome) {
+        effective[node.origin] = true;
+    }
+    effective
+}
+
+/// A genome that gives the same result as `genome` on every input, in no more VM steps,
+/// with its introns removed (see [`effective_instructions`]). The exception is an input
+/// on which `genome` exceeds `max_steps`: a loop without its introns takes fewer steps
+/// per iteration, so the simplified genome may finish where the original ran out. A dead
+/// `STORE` that cannot be removed with the value it pops becomes a `POP`, and jump
+/// offsets are recomputed.
+/// A genome that does nothing at all becomes `LOAD 0`, which also outputs `x`, since an
+/// empty genome cannot be mutated or written as assembly.
+pub fn simplify(genome: &Genome) -> Genome {
+    let nodes = reduce(genome);
+    if nodes.is_empty() {
+        return Genome {
+            instructions: vec![Instruction {
+                op: "LOAD".to_string(),
+                arg: Some(0.0),
+            }],
+        };
+    }
+    let len = nodes.len();
+    let instructions = nodes
+        .into_iter()
+        .enumerate()
+        .map(|(pc, node)| match node.jump {
+            Some(Target::Index(target)) => Instruction {
+                arg: Some(target as f64 - pc as f64),
+                ..node.instr
+            },
+            Some(Target::End) => Instruction {
+                arg: Some((len - pc) as f64),
+                ..node.instr
+            },
+            // The jump only moved backwards, so its offset still lands before the start.
+            Some(Target::OutOfRange) | None => node.instr,
+        })
+        .collect();
+    Genome { instructions }
+}
+
+/// An instruction of a genome being simplified. Jump targets are tracked as indices into
+/// the current program, so removing instructions only needs to remap them.
+struct Node {
+    /// Position in the original genome.
+    origin: usize,
+    instr: Instruction,
+    /// Decoded form of `instr`; jump offsets in it go stale, `jump` is authoritative.
+    op: Op,
+    jump: Option<Target>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Target {
+    Index(usize),
+    /// Anywhere at or past the end, which finishes the program normally.
+    End,
+    /// Before the start, which makes the program invalid.
+    OutOfRange,
+}
+
+/// Registers read once the program finishes: r0 is the output when the stack is empty.
+const LIVE_AT_EXIT: u8 = 1;
+
+/// Applies [`simplify_pass`] until nothing changes.
+fn reduce(genome: &Genome) -> Vec<Node> {
+    let len = genome.instructions.len();
+    let program = Program::decode(genome);
+    let mut nodes: Vec<Node> = genome
+        .instructions
+        .iter()
+        .zip(program.ops())
+        .enumerate()
+        .map(|(pc, (instr, &op))| {
+            let jump = match op {
+                Op::Jmp(offset) | Op::Jz(offset) | Op::Jnz(offset) => {
+                    Some(match pc.checked_add_signed(offset) {
+                        Some(target) if target < len => Target::Index(target),
+                        Some(_) => Target::End,
+                        None => Target::OutOfRange,
+                    })
+                }
+                _ => None,
+            };
+            Node {
+                origin: pc,
+                instr: instr.clone(),
+                op,
+                jump,
+            }
+        })
+        .collect();
+    while simplify_pass(&mut nodes) {}
+    nodes
+}
+
+/// One round of intron removal; returns whether the program changed. Every rewrite keeps
+/// the effect on the stack and on registers that are read later, including which inputs
+/// fail, and never adds a step.
+fn simplify_pass(nodes: &mut Vec<Node>) -> bool {
+    let len = nodes.len();
+    let mut keep = reachable(nodes);
+    let live_out = live_registers(nodes);
+    let mut targeted = vec![false; len];
+    for (node, _) in nodes.iter().zip(&keep).filter(|(_, &k)| k) {
+        if let Some(Target::Index(target)) = node.jump {
+            targeted[target] = true;
+        }
+    }
+    let mut changed = keep.contains(&false);
+    for pc in 0..len {
+        if !keep[pc] {
+            continue;
+        }
+        let to_next = match nodes[pc].jump {
+            Some(Target::Index(target)) => target == pc + 1,
+            Some(Target::End) => pc + 1 == len,
+            _ => false,
+        };
+        let pops_pushed_value = pc > 0
+            && keep[pc - 1]
+            && !targeted[pc]
+            && matches!(nodes[pc - 1].op, Op::Push(_) | Op::Load(_));
+        match nodes[pc].op {
+            Op::Nop => keep[pc] = false,
+            Op::Halt if pc + 1 == len => keep[pc] = false,
+            Op::Jmp(_) if to_next => keep[pc] = false,
+            Op::Jz(_) | Op::Jnz(_) if to_next => rewrite_as_pop(&mut nodes[pc]),
+            Op::Store(reg) if live_out[pc] & (1 << reg) == 0 => rewrite_as_pop(&mut nodes[pc]),
+            Op::Pop if pops_pushed_value => {
+                keep[pc - 1] = false;
+                keep[pc] = false;
+            }
+            _ => continue,
+        }
+        changed = true;
+    }
+    if !changed {
+        return false;
+    }
+
+    // Jumps into removed code continue at the next kept instruction, which is where
+    // control would have ended up after running the removed ones.
+    let mut remap = vec![Target::End; len + 1];
+    let mut next_kept = keep.iter().filter(|&&k| k).count();
+    for pc in (0..len).rev() {
+        if keep[pc] {
+            next_kept -= 1;
+            remap[pc] = Target::Index(next_kept);
+        } else {
+            remap[pc] = remap[pc + 1];
+        }
+    }
+    let old = std::mem::take(nodes);
+    for (mut node, kept) in old.into_iter().zip(keep) {
+        if kept {
+            if let Some(Target::Index(target)) = node.jump {
+                node.jump = Some(remap[target]);
+            }
+            nodes.push(node);
+        }
+    }
+    true
+}
+
+fn rewrite_as_pop(node: &mut Node) {
+    node.instr = Instruction {
+        op: "POP".to_string(),
+        arg: None,
+    };
+    node.op = Op::Pop;
+    node.jump = None;
+}
+
+/// Where control can go after `pc`: `Some(next)` or `None` for finishing normally.
+/// Faults and jumps before the start make the program invalid and have no successors.
+fn successors(nodes: &[Node], pc: usize) -> Vec<Option<usize>> {
+    let next = (pc + 1 < nodes.len()).then_some(pc + 1);
+    let jump = match nodes[pc].jump {
+        Some(Target::Index(target)) => vec![Some(target)],
+        Some(Target::End) => vec![None],
+        Some(Target::OutOfRange) | None => Vec::new(),
+    };
+    match nodes[pc].op {
+        Op::Halt => vec![None],
+        Op::Fault(_) => Vec::new(),
+        Op::Jmp(_) => jump,
+        Op::Jz(_) | Op::Jnz(_) => jump.into_iter().chain([next]).collect(),
+        _ => vec![next],
+    }
+}
+
+fn reachable(nodes: &[Node]) -> Vec<bool> {
+    let mut seen = vec![false; nodes.len()];
+    let mut stack = if nodes.is_empty() { Vec::new() } else { vec![0] };
+    while let Some(pc) = stack.pop() {
+        if std::mem::replace(&mut seen[pc], true) {
+            continue;
+        }
+        stack.extend(successors(nodes, pc).into_iter().flatten());
+    }
+    seen
+}
+
+/// Bitmask of the registers that may be loaded after each instruction runs, before
+/// being stored to again.
+fn live_registers(nodes: &[Node]) -> Vec<u8> {
+    let mut live_in = vec![0u8; nodes.len()];
+    let mut live_out = vec![0u8; nodes.len()];
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for pc in (0..nodes.len()).rev() {
+            let out = successors(nodes, pc)
+                .into_iter()
+                .map(|next| next.map_or(LIVE_AT_EXIT, |next| live_in[next]))
+                .fold(0, |acc, live| acc | live);
+            let live = match nodes[pc].op {
+                Op::Load(reg) => out | 1 << reg,
+                Op::Store(reg) => out & !(1 << reg),
+                _ => out,
+            };
+            if out != live_out[pc] || live != live_in[pc] {
+                live_out[pc] = out;
+                live_in[pc] = live;
+                changed = true;
+            }
+        }
+    }
+    live_out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::rng::seeded_rng;
+
+    fn tagged(tag: f64, len: usize) -> Genome {
+        Genome {
+            instructions: (0..len)
+                .map(|_| Instruction {
+                    op: "PUSH".to_string(),
+                    arg: Some(tag),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn crossover_only_uses_parent_material() {
+        let a = tagged(1.0, 10);
+        let b = tagged(2.0, 6);
+        let mut rng = seeded_rng(9);
+        for kind in [
+            CrossoverKind::OnePoint,
+            CrossoverKind::TwoPoint,
+            CrossoverKind::Uniform,
+            CrossoverKind::Homologous,
+        ] {
+            for _ in 0..50 {
+                let child = crossover(&a, &b, kind, &mut rng);
+                assert!(!child.instructions.is_empty());
+                assert!(child.instructions.len() <= ABS_MAX_LEN);
+                assert!(child
+                    .instructions
+                    .iter()
+                    .all(|i| i.arg == Some(1.0) || i.arg == Some(2.0)));
+            }
+        }
+    }
+
+    #[test]
+    fn homologous_keeps_positions() {
+        let a = tagged(1.0, 8);
+        let b = tagged(2.0, 8);
+        let mut rng = seeded_rng(1);
+        for _ in 0..50 {
+            let child = crossover(&a, &b, CrossoverKind::Homologous, &mut rng);
+            assert_eq!(child.instructions.len(), 8);
+            let switch = child
+                .instructions
+                .iter()
+                .position(|i| i.arg == Some(2.0))
+                .unwrap_or(8);
+            assert!(child.instructions[switch..].iter().all(|i| i.arg == Some(2.0)));
+        }
+    }
+
+    #[test]
+    fn uniform_keeps_first_parent_length() {
+        let a = tagged(1.0, 5);
+        let b = tagged(2.0, 12);
+        let mut rng = seeded_rng(4);
+        let child = crossover(&a, &b, CrossoverKind::Uniform, &mut rng);
+        assert_eq!(child.instructions.len(), 5);
+    }
+
+    #[test]
+    fn equality_and_hash_compare_args_by_bits() {
+        let hash = |g: &Genome| {
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            g.hash(&mut hasher);
+            hasher.finish()
+        };
+        assert_eq!(tagged(f64::NAN, 3), tagged(f64::NAN, 3));
+        assert_eq!(hash(&tagged(f64::NAN, 3)), hash(&tagged(f64::NAN, 3)));
+        assert_ne!(tagged(0.0, 3), tagged(-0.0, 3));
+        assert_ne!(tagged(1.0, 3), tagged(1.0, 4));
+        let mut popped = tagged(1.0, 3);
+        popped.instructions[1].op = "POP".to_string();
+        assert_ne!(popped, tagged(1.0, 3));
+    }
+
+    #[test]
+    fn replace_duplicates_keeps_first_occurrences() {
+        let mut rng = seeded_rng(6);
+        let ops = OpSet::all();
+        let mut population = vec![tagged(1.0, 3), tagged(2.0, 3), tagged(1.0, 3), tagged(1.0, 3)];
+        assert_eq!(replace_duplicates(&mut population, &mut rng, &ops), 2);
+        assert_eq!(population[..2], [tagged(1.0, 3), tagged(2.0, 3)]);
+        assert_eq!(population.iter().collect::<HashSet<_>>().len(), 4);
+        assert_eq!(replace_duplicates(&mut population, &mut rng, &ops), 0);
+    }
+
+    #[test]
+    fn simplify_removes_introns_and_retargets_jumps() {
+        let mut genome = parse_assembly(
+            "LOAD 0\nNOP\nPUSH 3.0\nSTORE 2\nJMP next\nnext: DUP\nMUL\nHALT\nLOAD 2\nNOP",
+        )
+        .unwrap();
+        // Code after HALT may not even decode.
+        genome.instructions[9].op = "FROB".to_string();
+        assert_eq!(
+            effective_instructions(&genome),
+            [true, false, false, false, false, true, true, false, false, false]
+        );
+        assert_eq!(simplify(&genome), parse_assembly("LOAD 0\nDUP\nMUL").unwrap());
+
+        let branchy = parse_assembly(
+            "LOAD 0\nJZ zero\nNOP\nPUSH 1.0\nJMP end\nzero: NOP\nPUSH 2.0\nend:",
+        )
+        .unwrap();
+        let expected = parse_assembly("LOAD 0\nJZ +3\nPUSH 1.0\nJMP +2\nPUSH 2.0").unwrap();
+        assert_eq!(simplify(&branchy), expected);
+
+        // r1 is loaded again, so only the store to r3 goes.
+        let live = parse_assembly("LOAD 0\nSTORE 1\nPUSH 2.0\nSTORE 3\nLOAD 1").unwrap();
+        assert_eq!(simplify(&live), parse_assembly("LOAD 0\nSTORE 1\nLOAD 1").unwrap());
+        let idle = parse_assembly("NOP\nHALT").unwrap();
+        assert_eq!(simplify(&idle), parse_assembly("LOAD 0").unwrap());
+    }
+
+    #[test]
+    fn simplified_loops_can_finish_within_max_steps() {
+        use crate::models::vm::{run_genome, VmConfig, VmOutcome};
+
+        let text = "PUSH 50\nloop: NOP\nNOP\nPUSH 1\nSUB\nDUP\nJNZ loop";
+        let genome = parse_assembly(text).unwrap();
+        let simple = simplify(&genome);
+        let run = |g: &Genome, max_steps| {
+            format!("{:?}", run_genome(g, 0.0, VmConfig { max_steps }))
+        };
+        assert_eq!(
+            run(&genome, 256),
+            format!("{:?}", VmOutcome::Invalid { reason: "max steps exceeded".to_string() })
+        );
+        assert_eq!(
+            run(&simple, 256),
+            format!("{:?}", VmOutcome::Ok { output: 0.0, steps: 201 })
+        );
+        assert_eq!(
+            run(&genome, 400),
+            format!("{:?}", VmOutcome::Ok { output: 0.0, steps: 301 })
+        );
+    }
+
+    #[test]
+    fn simplified_genomes_behave_like_the_original() {
+        use crate::models::vm::{Vm, VmConfig, VmOutcome};
+
+        let mut rng = seeded_rng(8);
+        let ops = OpSet::all();
+        let cfg = VmConfig { max_steps: 1000 };
+        let mut vm = Vm::default();
+        let (mut shortened, mut valid) = (0, 0);
+        for _ in 0..2000 {
+            let genome = random_genome(&mut rng, &ops);
+            let simple = simplify(&genome);
+            assert_eq!(simplify(&simple), simple);
+            if simple.instructions.len() < genome.instructions.len() {
+                shortened += 1;
+            }
+            let (mut original, mut simplified) = (Program::lazy(&genome), Program::lazy(&simple));
+            for x in [-3.5, -1.0, 0.0, 0.5, 2.0, 7.0] {
+                match (vm.run(&mut original, x, cfg), vm.run(&mut simplified, x, cfg)) {
+                    (VmOutcome::Ok { output, steps }, VmOutcome::Ok { output: o, steps: s }) => {
+                        assert_eq!(output.to_bits(), o.to_bits());
+                        assert!(s <= steps);
+                        valid += 1;
+                    }
+                    (VmOutcome::Invalid { reason }, VmOutcome::Invalid { reason: r })
+                        if reason == r => {}
+                    // Only the original ran out of steps. Each simplified step stands for at
+                    // most a genome's length of original ones, so with that much more room
+                    // the original has to end the same way.
+                    (VmOutcome::Invalid { reason }, b) if reason == "max steps exceeded" => {
+                        let roomy = VmConfig {
+                            max_steps: cfg.max_steps * ABS_MAX_LEN,
+                        };
+                        match (vm.run(&mut original, x, roomy), b) {
+                            (VmOutcome::Ok { output, .. }, VmOutcome::Ok { output: o, .. }) => {
+                                assert_eq!(output.to_bits(), o.to_bits());
+                            }
+                            (VmOutcome::Invalid { reason }, VmOutcome::Invalid { reason: r }) => {
+                                assert_eq!(reason, r);
+                            }
+                            (a, b) => panic!("{genome:?} on {x}: {a:?} became {b:?}"),
+                        }
+                    }
+                    (a, b) => panic!("{genome:?} on {x}: {a:?} became {b:?}"),
+                }
+            }
+        }
+        assert!(shortened > 1000 && valid > 300);
+    }
+
+    #[test]
+    fn assembly_round_trips_random_genomes() {
+        let mut rng = seeded_rng(4);
+        let ops = OpSet::all();
+        for _ in 0..200 {
+            let genome = random_genome(&mut rng, &ops);
+            assert_eq!(parse_assembly(&to_assembly(&genome)).unwrap(), genome);
+        }
+    }
+
+    #[test]
+    fn assembly_resolves_labels_and_skips_comments() {
+        let text = "; doubles x until it is non-zero\n\
+                    top:  load 0   # r0 holds x\n\
+                    0: PUSH 2.0\n\
+                    MUL\n\
+                    DUP\n\
+                    JZ top\n\
+                    JMP +2\n\
+                    NOP\n\
+                    end:\n";
+        let genome = parse_assembly(text).unwrap();
+        let args: Vec<Option<f64>> = genome.instructions.iter().map(|i| i.arg).collect();
+        assert_eq!(
+            args,
+            vec![Some(0.0), Some(2.0), None, None, Some(-4.0), Some(2.0), None]
+        );
+        assert_eq!(genome.instructions[0].op, "LOAD");
+        let printed = to_assembly(&genome);
+        assert!(printed.contains("JZ L0") && printed.ends_with("L1:\n"));
+    }
+
+    #[test]
+    fn assembly_reports_the_failing_line() {
+        assert_eq!(parse_assembly("PUSH 1\nFROB").unwrap_err(), "line 2: unknown op FROB");
+        assert_eq!(parse_assembly("JMP nowhere").unwrap_err(), "line 1: undefined label nowhere");
+        assert_eq!(parse_assembly("a:\na: NOP").unwrap_err(), "line 2: duplicate label a");
+        assert_eq!(parse_assembly("PUSH 1 2").unwrap_err(), "line 1: unexpected 2");
+        assert_eq!(parse_assembly("NOP\nPUSH").unwrap_err(), "line 2: PUSH needs an argument");
+        assert_eq!(parse_assembly("DUP 7").unwrap_err(), "line 1: DUP takes no argument");
+        assert_eq!(
+            parse_assembly("LOAD 9").unwrap_err(),
+            "line 1: LOAD needs a register from 0 to 3"
+        );
+        assert_eq!(
+            parse_assembly("JZ 1.5").unwrap_err(),
+            "line 1: JZ needs a label or a whole offset"
+        );
+        for value in ["nan", "inf", "-inf", "1e400"] {
+            assert_eq!(
+                parse_assembly(&format!("PUSH {value}")).unwrap_err(),
+                "line 1: PUSH needs a finite value"
+            );
+        }
+        assert!(parse_assembly("; nothing here").is_err());
+        for name in ["inf", "NaN", "Infinity"] {
+            assert_eq!(
+                parse_assembly(&format!("{name}: NOP")).unwrap_err(),
+                format!("line 1: invalid label {name}")
+            );
+        }
+    }
+
+    #[test]
+    fn assembly_of_invalid_args_is_rejected_on_the_way_back() {
+        let genome = Genome {
+            instructions: [("PUSH", Some(1.0)), ("LOAD", Some(1.5)), ("HALT", None)]
+                .into_iter()
+                .map(|(op, arg)| Instruction {
+                    op: op.to_string(),
+                    arg,
+                })
+                .collect(),
+        };
+        let printed = to_assembly(&genome);
+        assert_eq!(printed, "PUSH 1.0\nLOAD 1.5\nHALT\n");
+        assert_eq!(
+            parse_assembly(&printed).unwrap_err(),
+            "line 2: LOAD needs a register from 0 to 3"
+        );
+        let nan = Genome {
+            instructions: vec![Instruction {
+                op: "PUSH".to_string(),
+                arg: Some(f64::NAN),
+            }],
+        };
+        assert_eq!(
+            parse_assembly(&to_assembly(&nan)).unwrap_err(),
+            "line 1: PUSH needs a finite value"
+        );
+    }
+}
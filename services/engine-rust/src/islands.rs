@@ -0,0 +1,91 @@
+use std::ops::Range;
+
+use rand::Rng;
+
+use crate::models::rng::RunRng;
+use crate::models::{IslandConfig, MigrationTopology};
+
+/// Splits `total` individuals into `count` contiguous islands whose sizes differ by at most one.
+pub fn island_ranges(total: usize, count: usize) -> Vec<Range<usize>> {
+    let count = count.max(1);
+    let base = total / count;
+    let extra = total % count;
+    let mut start = 0;
+    (0..count)
+        .map(|i| {
+            let len = base + usize::from(i < extra);
+            let range = start..start + len;
+            start += len;
+            range
+        })
+        .collect()
+}
+
+/// Destination island for each source island.
+pub fn migration_targets(cfg: &IslandConfig, rng: &mut RunRng) -> Vec<usize> {
+    let count = cfg.count;
+    (0..count)
+        .map(|i| match cfg.topology {
+            MigrationTopology::Ring => (i + 1) % count,
+            MigrationTopology::Random => {
+                let d = rng.gen_range(0..count - 1);
+                if d >= i {
+                    d + 1
+                } else {
+                    d
+                }
+            }
+        })
+        .collect()
+}
+
+/// Global indices of the `k` fittest (or, with `worst`, least fit) members of `range`.
+pub fn ranked_members(fitness: &[f64], range: &Range<usize>, k: usize, worst: bool) -> Vec<usize> {
+    let mut members: Vec<usize> = range.clone().collect();
+    members.sort_by(|&a, &b| {
+        let ord = fitness[b]
+            .partial_cmp(&fitness[a])
+            .unwrap_or(std::cmp::Ordering::Equal);
+        if worst {
+            ord.reverse()
+        } else {
+            ord
+        }
+    });
+    members.truncate(k);
+    members
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::rng::seeded_rng;
+
+    #[test]
+    fn ranges_cover_population() {
+        let ranges = island_ranges(10, 3);
+        assert_eq!(ranges, vec![0..4, 4..7, 7..10]);
+    }
+
+    #[test]
+    fn random_topology_never_targets_self() {
+        let cfg = IslandConfig {
+            count: 4,
+            migration_interval: 1,
+            migrants: 1,
+            topology: MigrationTopology::Random,
+        };
+        let mut rng = seeded_rng(3);
+        for _ in 0..50 {
+            let targets = migration_targets(&cfg, &mut rng);
+            assert!(targets.iter().enumerate().all(|(i, &t)| i != t && t < 4));
+        }
+    }
+
+    #[test]
+    fn ranked_members_picks_extremes() {
+        let fitness = [0.1, 0.9, 0.5, 0.3, 0.8];
+        assert_eq!(ranked_members(&fitness, &(1..5), 2, false), vec![1, 4]);
+        assert_eq!(ranked_members(&fitness, &(1..5), 2, true), vec![3, 2]);
+    }
+}
@@ -1,21 +1,42 @@
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
 
 #[path = "genome.rs"]
 pub mod genome;
 #[path = "vm.rs"]
 pub mod vm;
+#[path = "bytecode.rs"]
+pub mod bytecode;
 #[path = "rng.rs"]
 pub mod rng;
 #[path = "evolve.rs"]
 pub mod evolve;
+#[path = "fitness.rs"]
+pub mod fitness;
+#[path = "selection.rs"]
+pub mod selection;
+#[path = "nsga.rs"]
+pub mod nsga;
+#[path = "islands.rs"]
+pub mod islands;
+#[path = "evaluator.rs"]
+pub mod evaluator;
+#[path = "storage.rs"]
+pub mod storage;
+#[path = "stats.rs"]
+pub mod stats;
+#[path = "cache.rs"]
+pub mod cache;
 
+/// Compares and hashes `arg` by its bits; see the impls in the genome module.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Instruction {
     pub op: String,
     pub arg: Option<f64>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Genome {
     pub instructions: Vec<Instruction>,
 }
@@ -27,6 +48,160 @@ pub struct RunConfig {
     pub generations: i64,
     pub mutation_rate: f64,
     pub task: String,
+    #[serde(default)]
+    pub evaluator: EvaluatorKind,
+    #[serde(default)]
+    pub allowed_ops: Option<Vec<String>>,
+    #[serde(default)]
+    pub op_weights: Option<HashMap<String, f64>>,
+    #[serde(default)]
+    pub crossover_rate: f64,
+    #[serde(default)]
+    pub crossover: CrossoverKind,
+    #[serde(default)]
+    pub selection: SelectionConfig,
+    #[serde(default)]
+    pub mode: EvolutionMode,
+    #[serde(default)]
+    pub islands: Option<IslandConfig>,
+    #[serde(default)]
+    pub stop: StopCriteria,
+    /// Genomes in assembly syntax that replace the first individuals of the initial population.
+    #[serde(default)]
+    pub seed_genomes: Option<Vec<String>>,
+    /// Replace repeated genomes in each new generation (within each island) with random ones.
+    #[serde(default)]
+    pub replace_duplicates: bool,
+}
+
+/// Extra termination conditions on top of `RunConfig.generations`; unset ones never fire.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct StopCriteria {
+    /// Stop once the best fitness reaches this value.
+    #[serde(default)]
+    pub target_fitness: Option<f64>,
+    /// Stop after this many generations without the best fitness improving.
+    #[serde(default)]
+    pub stagnation_generations: Option<u32>,
+    /// Stop once generations have taken this long in total, in seconds.
+    #[serde(default)]
+    pub max_wall_time_secs: Option<f64>,
+    /// Stop once this many genomes have been sent to the evaluator.
+    #[serde(default)]
+    pub max_evaluations: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StopReason {
+    GenerationLimit,
+    TargetFitness,
+    Stagnation,
+    MaxWallTime,
+    MaxEvaluations,
+}
+
+impl StopReason {
+    pub fn description(self) -> &'static str {
+        match self {
+            StopReason::GenerationLimit => "reached its generation limit",
+            StopReason::TargetFitness => "reached its target fitness",
+            StopReason::Stagnation => "stopped improving",
+            StopReason::MaxWallTime => "used up its wall-time budget",
+            StopReason::MaxEvaluations => "used up its evaluation budget",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IslandConfig {
+    pub count: usize,
+    pub migration_interval: u32,
+    pub migrants: usize,
+    #[serde(default)]
+    pub topology: MigrationTopology,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MigrationTopology {
+    /// Island `i` sends its migrants to island `i + 1`.
+    #[default]
+    Ring,
+    /// Each island sends its migrants to a different, randomly chosen island.
+    Random,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EvolutionMode {
+    /// Single objective: maximize fitness with elitism and `selection`.
+    #[default]
+    Single,
+    /// NSGA-II over error, instruction count and executed steps.
+    Nsga2,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CrossoverKind {
+    #[default]
+    OnePoint,
+    TwoPoint,
+    Uniform,
+    Homologous,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EvaluatorKind {
+    #[default]
+    Http,
+    Native,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum SelectionConfig {
+    Tournament {
+        #[serde(default = "default_tournament_k")]
+        k: usize,
+    },
+    Roulette,
+    Rank,
+    Truncation {
+        fraction: f64,
+    },
+    Sus,
+    Lexicase,
+    EpsilonLexicase {
+        #[serde(default)]
+        epsilon: Option<f64>,
+    },
+}
+
+impl Default for SelectionConfig {
+    fn default() -> Self {
+        SelectionConfig::Tournament {
+            k: default_tournament_k(),
+        }
+    }
+}
+
+fn default_tournament_k() -> usize {
+    3
+}
+
+/// Background execution state. `idle` runs only move when stepped by a client.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RunStatus {
+    #[default]
+    Idle,
+    Running,
+    Paused,
+    Finished,
+    Failed,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -40,22 +215,208 @@ pub struct RunState {
     pub generations: u32,
     pub mutation_rate: f32,
     pub task: String,
+    pub evaluator: EvaluatorKind,
+    pub allowed_ops: Option<Vec<String>>,
+    pub op_weights: Option<HashMap<String, f64>>,
+    pub crossover_rate: f64,
+    pub crossover: CrossoverKind,
+    pub selection: SelectionConfig,
+    pub mode: EvolutionMode,
+    pub islands: Option<IslandConfig>,
+    pub island_best_fitness: Option<Vec<f64>>,
+    pub stop: StopCriteria,
+    pub status: RunStatus,
+    /// Why the background runner failed, when `status` is `failed`.
+    pub error: Option<String>,
+    /// Which criterion finished the run, once `status` is `finished`.
+    pub stop_reason: Option<StopReason>,
+    /// Genomes sent to the evaluator so far, including the initial population. Cache
+    /// hits are not counted.
+    pub evaluations: u64,
+    /// Seconds spent creating and stepping the run.
+    pub elapsed_secs: f64,
+    /// Individuals answered by the run's fitness cache instead of the evaluator.
+    pub cache_hits: u64,
+    /// `cache_hits` as a fraction of all individuals scored, `evaluations + cache_hits`.
+    pub cache_hit_rate: f64,
+    pub replace_duplicates: bool,
+    /// Distinct genomes in the current population.
+    pub unique_count: u32,
+    /// Repeated genomes swapped for random ones so far, with `replace_duplicates` set.
+    pub duplicates_replaced: u64,
 }
 
+/// Pushed to `/events` and `/ws` subscribers after every generation and status change.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct RunHistoryPoint {
+pub struct RunEvent {
+    pub run_id: String,
     pub generation: u32,
     pub best_fitness: f64,
+    pub mean_fitness: f64,
+    pub best_genome: Genome,
+    pub status: RunStatus,
 }
 
+/// Population statistics for one generation.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunHistoryPoint {
+    pub generation: u32,
+    pub best_fitness: f64,
+    pub mean_fitness: f64,
+    pub median_fitness: f64,
+    /// Population standard deviation.
+    pub std_fitness: f64,
+    pub worst_fitness: f64,
+    pub invalid_fraction: f64,
+    pub mean_length: f64,
+    pub unique_count: u32,
+    /// Fraction of the generation's newly scored genomes that the fitness cache answered.
+    pub cache_hit_rate: f64,
+    /// Repeated genomes swapped for random ones before the generation was scored.
+    pub duplicates_replaced: u32,
+}
+
+/// `points` is the requested page of the run's history, each holding `generation` plus
+/// the fields selected with `?fields=`, all by default.
+#[derive(Debug, Clone, Serialize)]
 pub struct RunHistoryResponse {
     pub run_id: String,
     pub task: String,
-    pub points: Vec<RunHistoryPoint>,
+    /// Points recorded so far, one per generation.
+    pub total: usize,
+    pub points: Vec<PartialHistoryPoint>,
+}
+
+/// A [`RunHistoryPoint`] with only the fields a history request selected.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PartialHistoryPoint {
+    pub generation: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub best_fitness: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mean_fitness: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub median_fitness: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub std_fitness: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub worst_fitness: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub invalid_fraction: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mean_length: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unique_count: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_hit_rate: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duplicates_replaced: Option<u32>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EvaluateRequest {
+    pub genome: Genome,
+    pub inputs: Vec<f64>,
+    #[serde(default)]
+    pub vm: vm::VmConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EvaluateQuery {
+    #[serde(default)]
+    pub trace: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EvaluateResponse {
+    pub results: Vec<EvaluateResult>,
+}
+
+/// One input's outcome: `status` is `ok` with `output` and `steps`, or `invalid` with `reason`.
+#[derive(Debug, Serialize)]
+pub struct EvaluateResult {
+    pub input: f64,
+    #[serde(flatten)]
+    pub outcome: vm::VmOutcome,
+    /// Only present when the request asked for `trace=true`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trace: Option<vm::VmTrace>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RunHistoryQuery {
+    pub fields: Option<String>,
+    /// Index of the first point to return; 0 is the initial population's.
+    #[serde(default)]
+    pub offset: usize,
+    /// Points per page, at most 1000 and 1000 by default.
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParetoPoint {
+    pub genome: Genome,
+    pub fitness: f64,
+    pub error: f64,
+    pub length: u32,
+    pub steps: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParetoResponse {
+    pub run_id: String,
+    pub generation: u32,
+    pub front: Vec<ParetoPoint>,
+}
+
+/// The run's best genome next to its intron-free equivalent from [`genome::simplify`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BestGenomeResponse {
+    pub run_id: String,
+    pub generation: i64,
+    pub best_fitness: f64,
+    pub genome: Genome,
+    /// One flag per instruction of `genome`; `false` marks an intron.
+    pub effective: Vec<bool>,
+    pub simplified: Genome,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RunAdvanceRequest {
     pub steps: u32,
 }
+
+/// Everything needed to rebuild a run exactly, including RNG positions. Derived state
+/// (op set, NSGA-II ranks) is recomputed on load. `null` case errors stand for invalid.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunSnapshot {
+    pub version: u32,
+    /// Set on export; an import keeps it when free and otherwise needs a new id.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_id: Option<String>,
+    pub config: RunConfig,
+    pub generation: u32,
+    pub population: Vec<Genome>,
+    pub fitness: Vec<f64>,
+    pub case_errors: Vec<Vec<Option<f64>>>,
+    pub steps: Vec<f64>,
+    pub best_fitness: f64,
+    pub best_genome: Genome,
+    pub history: Vec<RunHistoryPoint>,
+    pub rng: rng::RunRng,
+    pub island_rngs: Vec<rng::RunRng>,
+    #[serde(default)]
+    pub status: RunStatus,
+    #[serde(default)]
+    pub error: Option<String>,
+    #[serde(default)]
+    pub stop_reason: Option<StopReason>,
+    #[serde(default)]
+    pub evaluations: u64,
+    #[serde(default)]
+    pub elapsed_secs: f64,
+    #[serde(default)]
+    pub cache_hits: u64,
+    #[serde(default)]
+    pub duplicates_replaced: u64,
+}
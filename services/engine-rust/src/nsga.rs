@@ -0,0 +1,259 @@
+use std::cmp::Ordering;
+
+use rand::Rng;
+
+use crate::models::fitness::is_invalid_fitness;
+use crate::models::rng::RunRng;
+use crate::models::Genome;
+
+pub const OBJECTIVE_COUNT: usize = 3;
+
+/// Minimization objectives for one individual: mean error, instruction count and
+/// mean executed steps. Invalid genomes are infeasible and lose to every feasible one.
+#[derive(Debug, Clone, Copy)]
+pub struct Objectives {
+    pub feasible: bool,
+    pub values: [f64; OBJECTIVE_COUNT],
+}
+
+impl Objectives {
+    pub fn new(fitness: f64, genome: &Genome, steps: f64) -> Self {
+        let feasible = !is_invalid_fitness(fitness);
+        // fitness = 1 / (1 + mean_error), so this recovers the mean error.
+        let error = if fitness > 0.0 {
+            1.0 / fitness - 1.0
+        } else {
+            f64::INFINITY
+        };
+        Self {
+            feasible,
+            values: [error, genome.instructions.len() as f64, steps],
+        }
+    }
+
+    pub fn error(&self) -> f64 {
+        self.values[0]
+    }
+
+    pub fn length(&self) -> f64 {
+        self.values[1]
+    }
+
+    pub fn steps(&self) -> f64 {
+        self.values[2]
+    }
+
+    /// Constrained domination: feasibility first, then Pareto dominance on the values.
+    pub fn dominates(&self, other: &Objectives) -> bool {
+        match (self.feasible, other.feasible) {
+            (true, false) => true,
+            (false, true) => false,
+            _ => {
+                let mut strictly_better = false;
+                for (a, b) in self.values.iter().zip(other.values.iter()) {
+                    if a > b {
+                        return false;
+                    }
+                    if a < b {
+                        strictly_better = true;
+                    }
+                }
+                strictly_better
+            }
+        }
+    }
+}
+
+/// Fast non-dominated sort; returns fronts of indices, best front first.
+pub fn non_dominated_sort(objs: &[Objectives]) -> Vec<Vec<usize>> {
+    let n = objs.len();
+    let mut dominated_by: Vec<Vec<usize>> = vec![Vec::new(); n];
+    let mut domination_count = vec![0usize; n];
+    let mut fronts: Vec<Vec<usize>> = vec![Vec::new()];
+
+    for p in 0..n {
+        for q in (p + 1)..n {
+            if objs[p].dominates(&objs[q]) {
+                dominated_by[p].push(q);
+                domination_count[q] += 1;
+            } else if objs[q].dominates(&objs[p]) {
+                dominated_by[q].push(p);
+                domination_count[p] += 1;
+            }
+        }
+    }
+    for (p, &count) in domination_count.iter().enumerate() {
+        if count == 0 {
+            fronts[0].push(p);
+        }
+    }
+
+    let mut i = 0;
+    while !fronts[i].is_empty() {
+        let mut next = Vec::new();
+        for &p in &fronts[i] {
+            for &q in &dominated_by[p] {
+                domination_count[q] -= 1;
+                if domination_count[q] == 0 {
+                    next.push(q);
+                }
+            }
+        }
+        fronts.push(next);
+        i += 1;
+    }
+    fronts.pop();
+    fronts
+}
+
+/// Crowding distance of each member of `front`, in the same order.
+pub fn crowding_distance(objs: &[Objectives], front: &[usize]) -> Vec<f64> {
+    let mut distance = vec![0.0; front.len()];
+    if front.len() <= 2 {
+        return vec![f64::INFINITY; front.len()];
+    }
+    for m in 0..OBJECTIVE_COUNT {
+        let mut order: Vec<usize> = (0..front.len()).collect();
+        order.sort_by(|&a, &b| {
+            objs[front[a]].values[m]
+                .partial_cmp(&objs[front[b]].values[m])
+                .unwrap_or(Ordering::Equal)
+        });
+        let first = objs[front[order[0]]].values[m];
+        let last = objs[front[order[order.len() - 1]]].values[m];
+        distance[order[0]] = f64::INFINITY;
+        distance[order[order.len() - 1]] = f64::INFINITY;
+        let span = last - first;
+        if !span.is_finite() || span <= 0.0 {
+            continue;
+        }
+        for w in 1..order.len() - 1 {
+            let prev = objs[front[order[w - 1]]].values[m];
+            let next = objs[front[order[w + 1]]].values[m];
+            distance[order[w]] += (next - prev) / span;
+        }
+    }
+    distance
+}
+
+/// Rank and crowding distance per individual.
+pub fn rank_and_crowding(objs: &[Objectives]) -> (Vec<usize>, Vec<f64>) {
+    let mut ranks = vec![0; objs.len()];
+    let mut crowding = vec![0.0; objs.len()];
+    for (rank, front) in non_dominated_sort(objs).iter().enumerate() {
+        for (&idx, d) in front.iter().zip(crowding_distance(objs, front)) {
+            ranks[idx] = rank;
+            crowding[idx] = d;
+        }
+    }
+    (ranks, crowding)
+}
+
+/// Picks `n` survivors: whole fronts while they fit, then the least crowded of the next front.
+pub fn select_survivors(objs: &[Objectives], n: usize) -> Vec<usize> {
+    let mut survivors = Vec::with_capacity(n);
+    for front in non_dominated_sort(objs) {
+        if survivors.len() + front.len() <= n {
+            survivors.extend(front);
+            continue;
+        }
+        let distance = crowding_distance(objs, &front);
+        let mut order: Vec<usize> = (0..front.len()).collect();
+        order.sort_by(|&a, &b| {
+            distance[b]
+                .partial_cmp(&distance[a])
+                .unwrap_or(Ordering::Equal)
+        });
+        let remaining = n - survivors.len();
+        survivors.extend(order.into_iter().take(remaining).map(|i| front[i]));
+        break;
+    }
+    survivors
+}
+
+/// Survivors of a merged parent and offspring set, with the rank and crowding distance
+/// each has within the surviving population.
+pub struct Survivors {
+    pub indices: Vec<usize>,
+    pub ranks: Vec<usize>,
+    pub crowding: Vec<f64>,
+}
+
+/// [`select_survivors`] followed by [`rank_and_crowding`] over the survivors.
+pub fn rank_survivors(objs: &[Objectives], n: usize) -> Survivors {
+    let indices = select_survivors(objs, n);
+    let kept: Vec<Objectives> = indices.iter().map(|&i| objs[i]).collect();
+    let (ranks, crowding) = rank_and_crowding(&kept);
+    Survivors {
+        indices,
+        ranks,
+        crowding,
+    }
+}
+
+/// Binary tournament on the crowded-comparison operator.
+pub fn crowded_tournament(ranks: &[usize], crowding: &[f64], rng: &mut RunRng) -> usize {
+    let len = ranks.len();
+    let a = rng.gen_range(0..len);
+    let b = rng.gen_range(0..len);
+    if ranks[a] != ranks[b] {
+        return if ranks[a] < ranks[b] { a } else { b };
+    }
+    if crowding[b] > crowding[a] {
+        b
+    } else {
+        a
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn obj(values: [f64; OBJECTIVE_COUNT]) -> Objectives {
+        Objectives {
+            feasible: true,
+            values,
+        }
+    }
+
+    #[test]
+    fn sorts_into_fronts() {
+        let objs = [
+            obj([1.0, 5.0, 5.0]),
+            obj([2.0, 2.0, 2.0]),
+            obj([2.0, 3.0, 3.0]),
+            obj([0.5, 9.0, 9.0]),
+        ];
+        let fronts = non_dominated_sort(&objs);
+        assert_eq!(fronts.len(), 2);
+        let mut first = fronts[0].clone();
+        first.sort();
+        assert_eq!(first, vec![0, 1, 3]);
+        assert_eq!(fronts[1], vec![2]);
+    }
+
+    #[test]
+    fn feasible_dominates_infeasible() {
+        let invalid = Objectives {
+            feasible: false,
+            values: [0.0, 1.0, 1.0],
+        };
+        let valid = obj([10.0, 50.0, 50.0]);
+        assert!(valid.dominates(&invalid));
+        assert!(!invalid.dominates(&valid));
+    }
+
+    #[test]
+    fn survivors_prefer_extremes_of_last_front() {
+        let objs = [
+            obj([0.0, 4.0, 0.0]),
+            obj([1.0, 3.0, 0.0]),
+            obj([1.1, 2.9, 0.0]),
+            obj([4.0, 0.0, 0.0]),
+        ];
+        let mut survivors = select_survivors(&objs, 2);
+        survivors.sort();
+        assert_eq!(survivors, vec![0, 3]);
+    }
+}
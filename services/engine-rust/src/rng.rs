@@ -1,13 +1,33 @@
-use rand::{rngs::StdRng, Rng, SeedableRng};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
 
-pub fn seeded_rng(seed: u64) -> StdRng {
-    StdRng::seed_from_u64(seed)
+/// Engine RNG. ChaCha12 is the algorithm behind `rand::rngs::StdRng`, so seeded runs
+/// produce the same stream, but this type can be serialized with the run.
+pub type RunRng = ChaCha12Rng;
+
+pub fn seeded_rng(seed: u64) -> RunRng {
+    RunRng::seed_from_u64(seed)
 }
 
-pub fn gen_range_f64(rng: &mut StdRng, min: f64, max: f64) -> f64 {
+pub fn gen_range_f64(rng: &mut RunRng, min: f64, max: f64) -> f64 {
     rng.gen_range(min..max)
 }
 
-pub fn gen_range_usize(rng: &mut StdRng, upper: usize) -> usize {
+pub fn gen_range_usize(rng: &mut RunRng, upper: usize) -> usize {
     rng.gen_range(0..upper)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+
+    #[test]
+    fn matches_std_rng_stream() {
+        let mut ours = seeded_rng(42);
+        let mut std = StdRng::seed_from_u64(42);
+        for _ in 0..64 {
+            assert_eq!(ours.gen::<u64>(), std.gen::<u64>());
+        }
+    }
+}
@@ -0,0 +1,302 @@
+use rand::{seq::SliceRandom, Rng};
+
+use crate::models::rng::RunRng;
+use crate::models::SelectionConfig;
+
+/// Parent selection for one generation. Built from the current fitness vector so
+/// per-generation work (sorting, cumulative sums) happens once, not per child.
+pub struct Selector<'a> {
+    fitness: &'a [f64],
+    case_errors: &'a [Vec<f64>],
+    method: Method,
+}
+
+enum Method {
+    Tournament { k: usize },
+    Weighted { cumulative: Vec<f64> },
+    Truncation { top: Vec<usize> },
+    Sus { cumulative: Vec<f64>, queue: Vec<usize> },
+    Lexicase { epsilons: Vec<f64> },
+}
+
+impl<'a> Selector<'a> {
+    pub fn new(cfg: &SelectionConfig, fitness: &'a [f64], case_errors: &'a [Vec<f64>]) -> Self {
+        let method = match *cfg {
+            SelectionConfig::Tournament { k } => Method::Tournament { k: k.max(1) },
+            SelectionConfig::Roulette => Method::Weighted {
+                cumulative: cumulative(&proportional_weights(fitness)),
+            },
+            SelectionConfig::Rank => Method::Weighted {
+                cumulative: cumulative(&rank_weights(fitness)),
+            },
+            SelectionConfig::Truncation { fraction } => {
+                let order = descending_order(fitness);
+                let keep = ((fitness.len() as f64 * fraction).ceil() as usize)
+                    .clamp(1, fitness.len().max(1));
+                Method::Truncation {
+                    top: order[..keep.min(order.len())].to_vec(),
+                }
+            }
+            SelectionConfig::Sus => Method::Sus {
+                cumulative: cumulative(&proportional_weights(fitness)),
+                queue: Vec::new(),
+            },
+            SelectionConfig::Lexicase => Method::Lexicase {
+                epsilons: vec![0.0; case_count(case_errors)],
+            },
+            SelectionConfig::EpsilonLexicase { epsilon } => Method::Lexicase {
+                epsilons: match epsilon {
+                    Some(eps) => vec![eps; case_count(case_errors)],
+                    None => median_absolute_deviations(case_errors),
+                },
+            },
+        };
+        Self {
+            fitness,
+            case_errors,
+            method,
+        }
+    }
+
+    pub fn select(&mut self, rng: &mut RunRng) -> usize {
+        let len = self.fitness.len();
+        if len == 0 {
+            return 0;
+        }
+        match &mut self.method {
+            Method::Tournament { k } => {
+                let mut best_idx = 0;
+                let mut best_fit = f64::MIN;
+                for _ in 0..*k {
+                    let idx = rng.gen_range(0..len);
+                    let fit = self.fitness[idx];
+                    if fit > best_fit {
+                        best_fit = fit;
+                        best_idx = idx;
+                    }
+                }
+                best_idx
+            }
+            Method::Weighted { cumulative } => spin(cumulative, rng.gen::<f64>(), rng),
+            Method::Truncation { top } => top[rng.gen_range(0..top.len())],
+            Method::Sus { cumulative, queue } => {
+                // One spin places `len` evenly spaced pointers; refill once they are used up.
+                if queue.is_empty() {
+                    let start = rng.gen::<f64>() / len as f64;
+                    *queue = (0..len)
+                        .map(|i| spin(cumulative, start + i as f64 / len as f64, rng))
+                        .collect();
+                    queue.shuffle(rng);
+                }
+                queue.pop().unwrap_or(0)
+            }
+            Method::Lexicase { epsilons } => {
+                let mut cases: Vec<usize> = (0..epsilons.len()).collect();
+                cases.shuffle(rng);
+                let mut candidates: Vec<usize> = (0..len).collect();
+                for case in cases {
+                    let best = candidates
+                        .iter()
+                        .map(|&i| case_error(self.case_errors, i, case))
+                        .fold(f64::INFINITY, f64::min);
+                    let threshold = best + epsilons[case];
+                    candidates.retain(|&i| case_error(self.case_errors, i, case) <= threshold);
+                    if candidates.len() == 1 {
+                        break;
+                    }
+                }
+                candidates[rng.gen_range(0..candidates.len())]
+            }
+        }
+    }
+}
+
+fn case_count(case_errors: &[Vec<f64>]) -> usize {
+    case_errors.iter().map(Vec::len).max().unwrap_or(0)
+}
+
+/// Missing entries count as infinitely wrong so they lose to any finite error.
+fn case_error(case_errors: &[Vec<f64>], idx: usize, case: usize) -> f64 {
+    case_errors
+        .get(idx)
+        .and_then(|row| row.get(case))
+        .copied()
+        .unwrap_or(f64::INFINITY)
+}
+
+/// Per-case epsilon for automatic epsilon-lexicase: the median absolute deviation of
+/// the population's finite errors on that case.
+fn median_absolute_deviations(case_errors: &[Vec<f64>]) -> Vec<f64> {
+    (0..case_count(case_errors))
+        .map(|case| {
+            let errors: Vec<f64> = (0..case_errors.len())
+                .map(|i| case_error(case_errors, i, case))
+                .filter(|e| e.is_finite())
+                .collect();
+            match median(errors.clone()) {
+                Some(m) => median(errors.iter().map(|e| (e - m).abs()).collect()).unwrap_or(0.0),
+                None => 0.0,
+            }
+        })
+        .collect()
+}
+
+fn median(mut values: Vec<f64>) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        Some((values[mid - 1] + values[mid]) / 2.0)
+    } else {
+        Some(values[mid])
+    }
+}
+
+/// Maps `u` in [0, 1) onto the cumulative weights; falls back to uniform when all weights are zero.
+fn spin(cumulative: &[f64], u: f64, rng: &mut RunRng) -> usize {
+    let total = cumulative.last().copied().unwrap_or(0.0);
+    if total <= 0.0 {
+        return rng.gen_range(0..cumulative.len());
+    }
+    let target = u * total;
+    cumulative
+        .partition_point(|&c| c <= target)
+        .min(cumulative.len() - 1)
+}
+
+fn cumulative(weights: &[f64]) -> Vec<f64> {
+    let mut total = 0.0;
+    weights
+        .iter()
+        .map(|w| {
+            total += w;
+            total
+        })
+        .collect()
+}
+
+/// Fitness shifted to be non-negative; non-finite values get no weight.
+fn proportional_weights(fitness: &[f64]) -> Vec<f64> {
+    let min = fitness
+        .iter()
+        .copied()
+        .filter(|f| f.is_finite())
+        .fold(f64::INFINITY, f64::min);
+    let shift = if min < 0.0 { -min } else { 0.0 };
+    fitness
+        .iter()
+        .map(|&f| if f.is_finite() { f + shift } else { 0.0 })
+        .collect()
+}
+
+/// Linear ranking: the worst individual weighs 1, the best weighs `len`.
+fn rank_weights(fitness: &[f64]) -> Vec<f64> {
+    let mut weights = vec![0.0; fitness.len()];
+    for (rank, idx) in descending_order(fitness).into_iter().rev().enumerate() {
+        weights[idx] = (rank + 1) as f64;
+    }
+    weights
+}
+
+fn descending_order(fitness: &[f64]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..fitness.len()).collect();
+    order.sort_by(|&a, &b| {
+        fitness[b]
+            .partial_cmp(&fitness[a])
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::rng::seeded_rng;
+
+    fn counts(cfg: SelectionConfig, fitness: &[f64], draws: usize) -> Vec<usize> {
+        lexicase_counts(cfg, fitness, &[], draws)
+    }
+
+    fn lexicase_counts(
+        cfg: SelectionConfig,
+        fitness: &[f64],
+        case_errors: &[Vec<f64>],
+        draws: usize,
+    ) -> Vec<usize> {
+        let mut rng = seeded_rng(5);
+        let mut selector = Selector::new(&cfg, fitness, case_errors);
+        let mut counts = vec![0; fitness.len()];
+        for _ in 0..draws {
+            counts[selector.select(&mut rng)] += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn truncation_only_picks_top() {
+        let fitness = [0.1, 0.9, 0.5, 0.7];
+        let c = counts(SelectionConfig::Truncation { fraction: 0.5 }, &fitness, 200);
+        assert_eq!(c[0] + c[2], 0);
+        assert!(c[1] > 0 && c[3] > 0);
+    }
+
+    #[test]
+    fn roulette_never_picks_zero_weight() {
+        let fitness = [0.0, 1.0, 3.0];
+        let c = counts(SelectionConfig::Roulette, &fitness, 400);
+        assert_eq!(c[0], 0);
+        assert!(c[2] > c[1]);
+    }
+
+    #[test]
+    fn rank_prefers_better_individuals() {
+        let fitness = [0.3, 0.1, 0.2];
+        let c = counts(SelectionConfig::Rank, &fitness, 600);
+        assert!(c[0] > c[2] && c[2] > c[1]);
+    }
+
+    #[test]
+    fn sus_allocates_expected_counts() {
+        // Each batch of 4 pointers spans the wheel once, so a 50% share yields exactly 2 picks.
+        let fitness = [2.0, 1.0, 1.0, 0.0];
+        let c = counts(SelectionConfig::Sus, &fitness, 4);
+        assert_eq!(c, vec![2, 1, 1, 0]);
+    }
+
+    #[test]
+    fn tournament_of_population_size_is_strong() {
+        let fitness = [0.1, 0.2, 0.9, 0.3];
+        let c = counts(SelectionConfig::Tournament { k: 16 }, &fitness, 100);
+        assert!(c[2] > 90);
+    }
+
+    #[test]
+    fn lexicase_picks_specialists_not_generalists() {
+        // 0 and 1 each solve one case perfectly; 2 is mediocre on both and has the best mean.
+        let fitness = [0.0, 0.0, 1.0];
+        let errors = vec![vec![0.0, 10.0], vec![10.0, 0.0], vec![4.0, 4.0]];
+        let c = lexicase_counts(SelectionConfig::Lexicase, &fitness, &errors, 200);
+        assert_eq!(c[2], 0);
+        assert!(c[0] > 0 && c[1] > 0);
+    }
+
+    #[test]
+    fn epsilon_lexicase_tolerates_near_ties() {
+        let fitness = [0.0, 0.0, 0.0];
+        let errors = vec![vec![0.0], vec![0.05], vec![f64::INFINITY]];
+        let strict = lexicase_counts(SelectionConfig::Lexicase, &fitness, &errors, 100);
+        assert_eq!(strict, vec![100, 0, 0]);
+        let cfg = SelectionConfig::EpsilonLexicase { epsilon: Some(0.1) };
+        let relaxed = lexicase_counts(cfg, &fitness, &errors, 100);
+        assert!(relaxed[0] > 0 && relaxed[1] > 0);
+        assert_eq!(relaxed[2], 0);
+    }
+
+    #[test]
+    fn automatic_epsilon_is_median_absolute_deviation() {
+        let errors = vec![vec![1.0], vec![2.0], vec![4.0], vec![f64::INFINITY]];
+        assert_eq!(median_absolute_deviations(&errors), vec![1.0]);
+    }
+}
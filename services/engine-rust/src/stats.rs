@@ -0,0 +1,157 @@
+use std::collections::HashSet;
+
+use crate::models::fitness::is_invalid_fitness;
+use crate::models::{Genome, PartialHistoryPoint, RunHistoryPoint};
+
+/// Fields of [`RunHistoryPoint`] that `GET /runs/{id}/history?fields=` can select.
+/// `generation` is always included.
+pub const HISTORY_FIELDS: [&str; 10] = [
+    "best_fitness",
+    "mean_fitness",
+    "median_fitness",
+    "std_fitness",
+    "worst_fitness",
+    "invalid_fraction",
+    "mean_length",
+    "unique_count",
+    "cache_hit_rate",
+    "duplicates_replaced",
+];
+
+/// Summary of one scored generation. `best_fitness` is the best of the current
+/// population; elitism keeps it from falling in single-objective mode, but NSGA-II can
+/// drop the fittest individual, so there it may fall between generations.
+pub fn generation_stats(
+    generation: u32,
+    population: &[Genome],
+    fitness: &[f64],
+    best_fitness: f64,
+) -> RunHistoryPoint {
+    let n = fitness.len().max(1) as f64;
+    let mean = fitness.iter().sum::<f64>() / n;
+    let variance = fitness.iter().map(|f| (f - mean).powi(2)).sum::<f64>() / n;
+    let mut sorted = fitness.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let median = match sorted.len() {
+        0 => 0.0,
+        len if len.is_multiple_of(2) => (sorted[len / 2 - 1] + sorted[len / 2]) / 2.0,
+        len => sorted[len / 2],
+    };
+    let invalid = fitness.iter().filter(|&&f| is_invalid_fitness(f)).count();
+    let total_length: usize = population.iter().map(|g| g.instructions.len()).sum();
+
+    RunHistoryPoint {
+        generation,
+        best_fitness,
+        mean_fitness: mean,
+        median_fitness: median,
+        std_fitness: variance.sqrt(),
+        worst_fitness: sorted.first().copied().unwrap_or(0.0),
+        invalid_fraction: invalid as f64 / n,
+        mean_length: total_length as f64 / population.len().max(1) as f64,
+        unique_count: unique_count(population) as u32,
+        cache_hit_rate: 0.0,
+        duplicates_replaced: 0,
+    }
+}
+
+/// Number of distinct genomes, comparing `PUSH` constants bit for bit.
+fn unique_count(population: &[Genome]) -> usize {
+    population.iter().collect::<HashSet<_>>().len()
+}
+
+/// Parses a comma-separated `fields` parameter; `None` selects every field.
+pub fn parse_fields(fields: Option<&str>) -> Result<Vec<&'static str>, String> {
+    let Some(fields) = fields else {
+        return Ok(HISTORY_FIELDS.to_vec());
+    };
+    fields
+        .split(',')
+        .map(str::trim)
+        .filter(|f| !f.is_empty())
+        .map(|f| {
+            HISTORY_FIELDS
+                .iter()
+                .find(|&&known| known == f)
+                .copied()
+                .ok_or_else(|| {
+                    format!(
+                        "unknown history field {}; expected any of {}",
+                        f,
+                        HISTORY_FIELDS.join(", ")
+                    )
+                })
+        })
+        .collect()
+}
+
+/// The point with `generation` and the selected fields only.
+pub fn select_fields(point: &RunHistoryPoint, fields: &[&str]) -> PartialHistoryPoint {
+    let has = |field: &str| fields.contains(&field);
+    PartialHistoryPoint {
+        generation: point.generation,
+        best_fitness: has("best_fitness").then_some(point.best_fitness),
+        mean_fitness: has("mean_fitness").then_some(point.mean_fitness),
+        median_fitness: has("median_fitness").then_some(point.median_fitness),
+        std_fitness: has("std_fitness").then_some(point.std_fitness),
+        worst_fitness: has("worst_fitness").then_some(point.worst_fitness),
+        invalid_fraction: has("invalid_fraction").then_some(point.invalid_fraction),
+        mean_length: has("mean_length").then_some(point.mean_length),
+        unique_count: has("unique_count").then_some(point.unique_count),
+        cache_hit_rate: has("cache_hit_rate").then_some(point.cache_hit_rate),
+        duplicates_replaced: has("duplicates_replaced").then_some(point.duplicates_replaced),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Instruction;
+
+    fn genome(args: &[f64]) -> Genome {
+        Genome {
+            instructions: args
+                .iter()
+                .map(|&a| Instruction {
+                    op: "PUSH".to_string(),
+                    arg: Some(a),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn summarizes_a_generation() {
+        let population = [
+            genome(&[1.0]),
+            genome(&[1.0]),
+            genome(&[2.0, 3.0]),
+            genome(&[-0.0]),
+        ];
+        let fitness = [0.5, 0.5, 1.0, 1e-9];
+        let stats = generation_stats(4, &population, &fitness, 1.0);
+        assert_eq!(stats.generation, 4);
+        assert!((stats.mean_fitness - 0.5).abs() < 1e-6);
+        assert_eq!(stats.median_fitness, 0.5);
+        assert!((stats.std_fitness - 0.125f64.sqrt()).abs() < 1e-6);
+        assert_eq!(stats.worst_fitness, 1e-9);
+        assert_eq!(stats.invalid_fraction, 0.25);
+        assert_eq!(stats.mean_length, 1.25);
+        assert_eq!(stats.unique_count, 3);
+    }
+
+    #[test]
+    fn selects_requested_fields() {
+        let point = generation_stats(1, &[genome(&[1.0])], &[0.5], 0.5);
+        let fields = parse_fields(Some("mean_fitness, unique_count")).unwrap();
+        let value = serde_json::to_value(select_fields(&point, &fields)).unwrap();
+        let mut keys: Vec<&String> = value.as_object().unwrap().keys().collect();
+        keys.sort();
+        assert_eq!(keys, ["generation", "mean_fitness", "unique_count"]);
+        let every = select_fields(&point, &parse_fields(None).unwrap());
+        let value = serde_json::to_value(every).unwrap();
+        assert_eq!(value, serde_json::to_value(&point).unwrap());
+        assert!(parse_fields(Some("best_fitness,fastest")).is_err());
+        assert_eq!(parse_fields(None).unwrap().len(), HISTORY_FIELDS.len());
+    }
+}
@@ -0,0 +1,250 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+
+use rusqlite::{params, Connection};
+use tokio::sync::oneshot;
+
+use crate::error::EngineError;
+use crate::models::{RunHistoryPoint, RunSnapshot};
+
+/// SQLite tables of run snapshots, one row per run overwritten every generation, and
+/// of history points, appended as they are recorded so a save does not grow with the run.
+/// Runs are loaded from it at startup; after that only a [`RunWriter`] thread uses it.
+pub struct RunDb {
+    conn: Mutex<Connection>,
+}
+
+impl RunDb {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, EngineError> {
+        let conn = Connection::open(path).map_err(db_error)?;
+        Self::init(conn)
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory() -> Result<Self, EngineError> {
+        Self::init(Connection::open_in_memory().map_err(db_error)?)
+    }
+
+    fn init(conn: Connection) -> Result<Self, EngineError> {
+        conn.execute_batch(
+            "PRAGMA journal_mode = WAL;
+             CREATE TABLE IF NOT EXISTS runs (
+                 run_id TEXT PRIMARY KEY,
+                 generation INTEGER NOT NULL,
+                 snapshot TEXT NOT NULL,
+                 updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+             );
+             CREATE TABLE IF NOT EXISTS history (
+                 run_id TEXT NOT NULL,
+                 seq INTEGER NOT NULL,
+                 point TEXT NOT NULL,
+                 PRIMARY KEY (run_id, seq)
+             );",
+        )
+        .map_err(db_error)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    /// Overwrites the run's snapshot and stores `history` points, each at its index in
+    /// the run's history, in one transaction.
+    pub fn save(
+        &self,
+        run_id: &str,
+        generation: u32,
+        snapshot: &str,
+        history: &[(usize, String)],
+    ) -> Result<(), EngineError> {
+        let mut conn = self.lock()?;
+        let tx = conn.transaction().map_err(db_error)?;
+        tx.execute(
+            "INSERT INTO runs (run_id, generation, snapshot) VALUES (?1, ?2, ?3)
+             ON CONFLICT(run_id) DO UPDATE SET
+                 generation = excluded.generation,
+                 snapshot = excluded.snapshot,
+                 updated_at = CURRENT_TIMESTAMP",
+            params![run_id, generation, snapshot],
+        )
+        .map_err(db_error)?;
+        for (seq, point) in history {
+            tx.execute(
+                "INSERT OR REPLACE INTO history (run_id, seq, point) VALUES (?1, ?2, ?3)",
+                params![run_id, *seq as i64, point],
+            )
+            .map_err(db_error)?;
+        }
+        tx.commit().map_err(db_error)
+    }
+
+    /// All stored `(run_id, snapshot)` pairs.
+    pub fn load_all(&self) -> Result<Vec<(String, String)>, EngineError> {
+        let conn = self.lock()?;
+        let mut stmt = conn
+            .prepare("SELECT run_id, snapshot FROM runs ORDER BY run_id")
+            .map_err(db_error)?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(db_error)?;
+        rows.collect::<Result<_, _>>().map_err(db_error)
+    }
+
+    /// Stored history points of one run, in order.
+    pub fn load_history(&self, run_id: &str) -> Result<Vec<String>, EngineError> {
+        let conn = self.lock()?;
+        let mut stmt = conn
+            .prepare("SELECT point FROM history WHERE run_id = ?1 ORDER BY seq")
+            .map_err(db_error)?;
+        let rows = stmt
+            .query_map([run_id], |row| row.get(0))
+            .map_err(db_error)?;
+        rows.collect::<Result<_, _>>().map_err(db_error)
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, Connection>, EngineError> {
+        self.conn
+            .lock()
+            .map_err(|_| EngineError::InternalError("database lock poisoned".to_string()))
+    }
+}
+
+/// State of one run to store: its snapshot without history, plus history points by index.
+pub struct RunSave {
+    pub run_id: String,
+    pub snapshot: RunSnapshot,
+    pub history: Vec<(usize, RunHistoryPoint)>,
+}
+
+enum WriterMessage {
+    Save(Box<RunSave>),
+    Flush(oneshot::Sender<()>),
+}
+
+/// Queue in front of a [`RunDb`], drained by one writer thread, so runs hand off their
+/// saves without waiting on serialization or SQLite. Saves that queue up for the same run
+/// are coalesced into one transaction, and history points of a failed save are kept and
+/// written with the run's next one.
+#[derive(Clone)]
+pub struct RunWriter {
+    queue: Sender<WriterMessage>,
+}
+
+impl RunWriter {
+    pub fn spawn(db: Arc<RunDb>) -> Self {
+        let (queue, saves) = mpsc::channel();
+        std::thread::Builder::new()
+            .name("run-writer".to_string())
+            .spawn(move || write_saves(&db, saves))
+            .expect("failed to spawn run writer thread");
+        Self { queue }
+    }
+
+    pub fn save(&self, save: RunSave) {
+        if self.queue.send(WriterMessage::Save(Box::new(save))).is_err() {
+            tracing::error!("run writer has stopped; dropping save");
+        }
+    }
+
+    /// Waits until every save queued before this call has been attempted.
+    pub async fn flush(&self) {
+        let (done, flushed) = oneshot::channel();
+        if self.queue.send(WriterMessage::Flush(done)).is_ok() {
+            let _ = flushed.await;
+        }
+    }
+}
+
+/// Serialized snapshot and history points waiting to be written for one run.
+#[derive(Default)]
+struct PendingWrite {
+    generation: u32,
+    snapshot: String,
+    history: BTreeMap<usize, String>,
+}
+
+/// Runs until every [`RunWriter`] handle is dropped.
+fn write_saves(db: &RunDb, saves: Receiver<WriterMessage>) {
+    let mut pending: HashMap<String, PendingWrite> = HashMap::new();
+    while let Ok(first) = saves.recv() {
+        let mut flushed = Vec::new();
+        for message in std::iter::once(first).chain(saves.try_iter()) {
+            match message {
+                WriterMessage::Save(save) => queue_save(&mut pending, *save),
+                WriterMessage::Flush(done) => flushed.push(done),
+            }
+        }
+        pending.retain(|run_id, write| {
+            let points: Vec<(usize, String)> =
+                write.history.iter().map(|(&seq, p)| (seq, p.clone())).collect();
+            match db.save(run_id, write.generation, &write.snapshot, &points) {
+                Ok(()) => false,
+                Err(err) => {
+                    tracing::error!("failed to persist run {}: {}", run_id, err);
+                    true
+                }
+            }
+        });
+        for done in flushed {
+            let _ = done.send(());
+        }
+    }
+}
+
+fn queue_save(pending: &mut HashMap<String, PendingWrite>, save: RunSave) {
+    let serialized = serde_json::to_string(&save.snapshot).and_then(|snapshot| {
+        let points = save
+            .history
+            .iter()
+            .map(|(seq, point)| Ok((*seq, serde_json::to_string(point)?)))
+            .collect::<Result<Vec<_>, serde_json::Error>>()?;
+        Ok((snapshot, points))
+    });
+    match serialized {
+        Ok((snapshot, points)) => {
+            let write = pending.entry(save.run_id).or_default();
+            write.generation = save.snapshot.generation;
+            write.snapshot = snapshot;
+            write.history.extend(points);
+        }
+        Err(err) => tracing::error!("failed to serialize run {}: {}", save.run_id, err),
+    }
+}
+
+fn db_error(err: rusqlite::Error) -> EngineError {
+    EngineError::InternalError(format!("database: {}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_overwrites_previous_snapshot() {
+        let db = RunDb::open_in_memory().unwrap();
+        db.save("a", 0, "{\"g\":0}", &[]).unwrap();
+        db.save("b", 0, "{}", &[]).unwrap();
+        db.save("a", 1, "{\"g\":1}", &[]).unwrap();
+        let rows = db.load_all().unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                ("a".to_string(), "{\"g\":1}".to_string()),
+                ("b".to_string(), "{}".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn history_points_accumulate_across_saves() {
+        let db = RunDb::open_in_memory().unwrap();
+        db.save("a", 0, "{}", &[(0, "p0".to_string())]).unwrap();
+        db.save("b", 0, "{}", &[(0, "q0".to_string())]).unwrap();
+        db.save("a", 2, "{}", &[(1, "p1".to_string()), (2, "p2".to_string())])
+            .unwrap();
+        assert_eq!(db.load_history("a").unwrap(), vec!["p0", "p1", "p2"]);
+        assert_eq!(db.load_history("b").unwrap(), vec!["q0"]);
+        assert!(db.load_history("c").unwrap().is_empty());
+    }
+}
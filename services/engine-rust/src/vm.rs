@@ -1,8 +1,14 @@
-use crate::models::genome::{parse_register_index, REGISTER_COUNT};
-use crate::models::{Genome, Instruction};
+use serde::{Deserialize, Serialize};
+
+use crate::models::bytecode::{Op, Program};
+use crate::models::genome::REGISTER_COUNT;
+use crate::models::Genome;
 
 const EPS_DIVISOR: f64 = 1e-12;
 
+/// Missing fields take their defaults, so API callers can override just what they need.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
 pub struct VmConfig {
     pub max_steps: usize,
 }
@@ -13,173 +19,294 @@ impl Default for VmConfig {
     }
 }
 
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
 pub enum VmOutcome {
-    Ok { output: f64 },
+    /// `steps` is the number of instructions executed.
+    Ok { output: f64, steps: usize },
     Invalid { reason: String },
 }
 
+/// Runs decoded programs, keeping one stack allocation across runs. Wrap a genome once
+/// with [`Program::lazy`] or [`Program::decode`] and reuse the program for every input.
+#[derive(Debug, Default)]
+pub struct Vm {
+    stack: Vec<f64>,
+}
+
+impl Vm {
+    pub fn run(&mut self, program: &mut Program, x: f64, cfg: VmConfig) -> VmOutcome {
+        execute(program, x, cfg, &mut self.stack, &mut NoTrace)
+    }
+}
+
+/// Runs a genome once. To run one genome on many inputs, reuse a [`Vm`] and [`Program`].
 pub fn run_genome(genome: &Genome, x: f64, cfg: VmConfig) -> VmOutcome {
+    Vm::default().run(&mut Program::lazy(genome), x, cfg)
+}
+
+/// Machine state just before one instruction ran.
+#[derive(Debug, Clone, Serialize)]
+pub struct TraceStep {
+    pub pc: usize,
+    pub op: String,
+    pub arg: Option<f64>,
+    pub stack: Vec<f64>,
+    pub registers: [f64; REGISTER_COUNT],
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct VmTrace {
+    pub steps: Vec<TraceStep>,
+    /// Index of the instruction that made the program invalid; for the step limit, the one
+    /// that would have run next. `None` for valid programs and non-finite outputs.
+    pub failed_at: Option<usize>,
+}
+
+/// Decodes and runs a genome, also recording every executed step.
+pub fn trace_genome(genome: &Genome, x: f64, cfg: VmConfig) -> (VmOutcome, VmTrace) {
+    let mut tracer = GenomeTracer {
+        genome,
+        trace: VmTrace::default(),
+    };
+    let outcome = execute(&mut Program::lazy(genome), x, cfg, &mut Vec::new(), &mut tracer);
+    (outcome, tracer.trace)
+}
+
+/// Observes execution; the untraced VM uses [`NoTrace`], which compiles away.
+trait Tracer {
+    fn step(&mut self, pc: usize, stack: &[f64], registers: &[f64; REGISTER_COUNT]);
+    fn fail(&mut self, pc: usize);
+}
+
+struct NoTrace;
+
+impl Tracer for NoTrace {
+    #[inline(always)]
+    fn step(&mut self, _pc: usize, _stack: &[f64], _registers: &[f64; REGISTER_COUNT]) {}
+    #[inline(always)]
+    fn fail(&mut self, _pc: usize) {}
+}
+
+struct GenomeTracer<'a> {
+    genome: &'a Genome,
+    trace: VmTrace,
+}
+
+impl Tracer for GenomeTracer<'_> {
+    fn step(&mut self, pc: usize, stack: &[f64], registers: &[f64; REGISTER_COUNT]) {
+        let instr = &self.genome.instructions[pc];
+        self.trace.steps.push(TraceStep {
+            pc,
+            op: instr.op.clone(),
+            arg: instr.arg,
+            stack: stack.to_vec(),
+            registers: *registers,
+        });
+    }
+
+    fn fail(&mut self, pc: usize) {
+        self.trace.failed_at = Some(pc);
+    }
+}
+
+fn execute(
+    program: &mut Program,
+    x: f64,
+    cfg: VmConfig,
+    stack: &mut Vec<f64>,
+    tracer: &mut impl Tracer,
+) -> VmOutcome {
     let mut registers = [0.0_f64; REGISTER_COUNT];
     registers[0] = x;
-    let mut stack: Vec<f64> = Vec::new();
+    stack.clear();
     let mut pc: usize = 0;
     let mut steps: usize = 0;
-    let instructions = &genome.instructions;
+    let len = program.len();
 
-    while pc < instructions.len() {
+    while pc < len {
         if steps >= cfg.max_steps {
+            tracer.fail(pc);
             return VmOutcome::Invalid {
                 reason: "max steps exceeded".to_string(),
             };
         }
 
-        let instr = &instructions[pc];
+        tracer.step(pc, stack, &registers);
         steps += 1;
 
-        match instr.op.as_str() {
-            "PUSH" => {
-                let val = match instr.arg {
-                    Some(v) => v,
-                    None => {
-                        return VmOutcome::Invalid {
-                            reason: "PUSH missing arg".to_string(),
-                        }
-                    }
-                };
-                stack.push(val);
-            }
-            "LOAD" => {
-                let idx = match parse_register_index(instr.arg) {
-                    Ok(i) => i,
-                    Err(e) => return VmOutcome::Invalid { reason: e },
-                };
-                stack.push(registers[idx]);
-            }
-            "STORE" => {
-                let idx = match parse_register_index(instr.arg) {
-                    Ok(i) => i,
-                    Err(e) => return VmOutcome::Invalid { reason: e },
-                };
-                let val = match stack.pop() {
-                    Some(v) => v,
-                    None => {
-                        return VmOutcome::Invalid {
-                            reason: "stack underflow".to_string(),
-                        }
-                    }
-                };
-                registers[idx] = val;
-            }
-            "ADD" => {
-                let (a, b) = match pop_two(&mut stack) {
-                    Some(vals) => vals,
-                    None => {
-                        return VmOutcome::Invalid {
-                            reason: "stack underflow".to_string(),
-                        }
-                    }
+        match step(program.op(pc), pc, stack, &mut registers) {
+            Ok(Flow::Next(next)) => pc = next,
+            Ok(Flow::Halt) => break,
+            Err(reason) => {
+                tracer.fail(pc);
+                return VmOutcome::Invalid {
+                    reason: reason.to_string(),
                 };
-                stack.push(a + b);
             }
-            "SUB" => {
-                let (a, b) = match pop_two(&mut stack) {
-                    Some(vals) => vals,
-                    None => {
-                        return VmOutcome::Invalid {
-                            reason: "stack underflow".to_string(),
-                        }
-                    }
-                };
-                stack.push(a - b);
+        }
+    }
+
+    let output = stack.last().copied().unwrap_or(registers[0]);
+    if !output.is_finite() {
+        return VmOutcome::Invalid {
+            reason: "non-finite output".to_string(),
+        };
+    }
+
+    VmOutcome::Ok { output, steps }
+}
+
+enum Flow {
+    Next(usize),
+    Halt,
+}
+
+/// Executes one instruction; the error is the reason the program is invalid.
+#[inline(always)]
+fn step(
+    op: Op,
+    pc: usize,
+    stack: &mut Vec<f64>,
+    registers: &mut [f64; REGISTER_COUNT],
+) -> Result<Flow, &'static str> {
+    match op {
+        Op::Push(val) => stack.push(val),
+        Op::Load(idx) => stack.push(registers[idx]),
+        Op::Store(idx) => registers[idx] = pop(stack)?,
+        Op::Add => {
+            let (a, b) = pop_two(stack)?;
+            stack.push(a + b);
+        }
+        Op::Sub => {
+            let (a, b) = pop_two(stack)?;
+            stack.push(a - b);
+        }
+        Op::Mul => {
+            let (a, b) = pop_two(stack)?;
+            stack.push(a * b);
+        }
+        Op::Div => {
+            let (a, b) = pop_two(stack)?;
+            if b.abs() < EPS_DIVISOR {
+                return Err("division by near-zero");
             }
-            "MUL" => {
-                let (a, b) = match pop_two(&mut stack) {
-                    Some(vals) => vals,
-                    None => {
-                        return VmOutcome::Invalid {
-                            reason: "stack underflow".to_string(),
-                        }
-                    }
-                };
-                stack.push(a * b);
+            stack.push(a / b);
+        }
+        Op::Sin => unary(stack, |a| Ok(a.sin()))?,
+        Op::Cos => unary(stack, |a| Ok(a.cos()))?,
+        Op::Exp => unary(stack, |a| {
+            let v = a.exp();
+            if !v.is_finite() {
+                return Err("exp overflow");
             }
-            "DIV" => {
-                let (a, b) = match pop_two(&mut stack) {
-                    Some(vals) => vals,
-                    None => {
-                        return VmOutcome::Invalid {
-                            reason: "stack underflow".to_string(),
-                        }
-                    }
-                };
-                if b.abs() < EPS_DIVISOR {
-                    return VmOutcome::Invalid {
-                        reason: "division by near-zero".to_string(),
-                    };
-                }
-                stack.push(a / b);
+            Ok(v)
+        })?,
+        Op::Log => unary(stack, |a| {
+            if a <= 0.0 {
+                return Err("log of non-positive");
             }
-            "DUP" => {
-                let top = match stack.last() {
-                    Some(v) => *v,
-                    None => {
-                        return VmOutcome::Invalid {
-                            reason: "stack underflow".to_string(),
-                        }
-                    }
-                };
-                stack.push(top);
+            Ok(a.ln())
+        })?,
+        Op::Sqrt => unary(stack, |a| {
+            if a < 0.0 {
+                return Err("sqrt of negative");
             }
-            "SWAP" => {
-                if stack.len() < 2 {
-                    return VmOutcome::Invalid {
-                        reason: "stack underflow".to_string(),
-                    };
-                }
-                let len = stack.len();
-                stack.swap(len - 1, len - 2);
+            Ok(a.sqrt())
+        })?,
+        Op::Abs => unary(stack, |a| Ok(a.abs()))?,
+        Op::Neg => unary(stack, |a| Ok(-a))?,
+        Op::Dup => {
+            let top = *stack.last().ok_or(UNDERFLOW)?;
+            stack.push(top);
+        }
+        Op::Swap => {
+            if stack.len() < 2 {
+                return Err(UNDERFLOW);
             }
-            "POP" => {
-                if stack.pop().is_none() {
-                    return VmOutcome::Invalid {
-                        reason: "stack underflow".to_string(),
-                    };
-                }
+            let len = stack.len();
+            stack.swap(len - 1, len - 2);
+        }
+        Op::Pop => {
+            pop(stack)?;
+        }
+        Op::Lt => compare(stack, |a, b| a < b)?,
+        Op::Gt => compare(stack, |a, b| a > b)?,
+        Op::Eq => compare(stack, |a, b| a == b)?,
+        Op::Jmp(offset) => return jump(pc, offset),
+        Op::Jz(offset) => {
+            if pop(stack)? == 0.0 {
+                return jump(pc, offset);
             }
-            "HALT" => break,
-            "NOP" => {}
-            _ => {
-                return VmOutcome::Invalid {
-                    reason: "unknown opcode".to_string(),
-                }
+        }
+        Op::Jnz(offset) => {
+            if pop(stack)? != 0.0 {
+                return jump(pc, offset);
             }
         }
-
-        pc += 1;
+        Op::Halt => return Ok(Flow::Halt),
+        Op::Nop => {}
+        Op::Fault(fault) => return Err(fault.reason()),
     }
+    Ok(Flow::Next(pc + 1))
+}
 
-    let output = stack.last().copied().unwrap_or(registers[0]);
-    if !output.is_finite() {
-        return VmOutcome::Invalid {
-            reason: "non-finite output".to_string(),
-        };
+const UNDERFLOW: &str = "stack underflow";
+
+/// Replaces the top of the stack. Infinite and NaN operands are invalid for every unary
+/// op, matching the fitness service.
+#[inline(always)]
+fn unary(
+    stack: &mut Vec<f64>,
+    f: impl FnOnce(f64) -> Result<f64, &'static str>,
+) -> Result<(), &'static str> {
+    let a = pop(stack)?;
+    if !a.is_finite() {
+        return Err("non-finite operand");
     }
+    stack.push(f(a)?);
+    Ok(())
+}
 
-    VmOutcome::Ok { output }
+/// Pops `a`, `b` and pushes `1.0` if `f(a, b)` holds, else `0.0`.
+#[inline(always)]
+fn compare(stack: &mut Vec<f64>, f: impl FnOnce(f64, f64) -> bool) -> Result<(), &'static str> {
+    let (a, b) = pop_two(stack)?;
+    stack.push(if f(a, b) { 1.0 } else { 0.0 });
+    Ok(())
 }
 
-fn pop_two(stack: &mut Vec<f64>) -> Option<(f64, f64)> {
+/// Targets past the end are allowed and simply end the program, like falling off it.
+fn jump(pc: usize, offset: isize) -> Result<Flow, &'static str> {
+    pc.checked_add_signed(offset)
+        .map(Flow::Next)
+        .ok_or("jump out of range")
+}
+
+fn pop(stack: &mut Vec<f64>) -> Result<f64, &'static str> {
+    stack.pop().ok_or(UNDERFLOW)
+}
+
+fn pop_two(stack: &mut Vec<f64>) -> Result<(f64, f64), &'static str> {
     if stack.len() < 2 {
-        return None;
+        return Err(UNDERFLOW);
     }
     let b = stack.pop().unwrap();
     let a = stack.pop().unwrap();
-    Some((a, b))
+    Ok((a, b))
 }
 
 #[cfg(test)]
 mod tests {
+    use std::time::Instant;
+
     use super::*;
+    use crate::models::evaluator::Evaluators;
+    use crate::models::evolve::{advance_run, create_run, get_snapshot, new_store};
+    use crate::models::fitness::get_cases;
+    use crate::models::genome::{parse_jump_offset, parse_register_index, random_genome, OpSet};
+    use crate::models::rng::seeded_rng;
+    use crate::models::Instruction;
 
     fn cfg() -> VmConfig {
         VmConfig::default()
@@ -206,7 +333,7 @@ mod tests {
             ("HALT", None),
         ]);
         match run_genome(&genome, 0.0, cfg()) {
-            VmOutcome::Ok { output } => assert_eq!(output, 5.0),
+            VmOutcome::Ok { output, .. } => assert_eq!(output, 5.0),
             VmOutcome::Invalid { reason } => panic!("unexpected invalid: {reason}"),
         }
     }
@@ -220,7 +347,7 @@ mod tests {
             ("HALT", None),
         ]);
         match run_genome(&genome, 4.0, cfg()) {
-            VmOutcome::Ok { output } => assert_eq!(output, 8.0),
+            VmOutcome::Ok { output, .. } => assert_eq!(output, 8.0),
             VmOutcome::Invalid { reason } => panic!("unexpected invalid: {reason}"),
         }
     }
@@ -229,7 +356,7 @@ mod tests {
     fn underflow_is_invalid() {
         let genome = genome_from_ops(vec![("ADD", None)]);
         match run_genome(&genome, 0.0, cfg()) {
-            VmOutcome::Ok { output } => panic!("expected invalid, got {output}"),
+            VmOutcome::Ok { output, .. } => panic!("expected invalid, got {output}"),
             VmOutcome::Invalid { .. } => {}
         }
     }
@@ -242,18 +369,370 @@ mod tests {
             ("DIV", None),
         ]);
         match run_genome(&genome, 0.0, cfg()) {
-            VmOutcome::Ok { output } => panic!("expected invalid, got {output}"),
+            VmOutcome::Ok { output, .. } => panic!("expected invalid, got {output}"),
             VmOutcome::Invalid { .. } => {}
         }
     }
 
+    #[test]
+    fn unary_math_ops() {
+        let cases = [
+            ("SIN", 0.0, 0.0),
+            ("COS", 0.0, 1.0),
+            ("EXP", 0.0, 1.0),
+            ("LOG", 1.0, 0.0),
+            ("SQRT", 9.0, 3.0),
+            ("ABS", -2.5, 2.5),
+            ("NEG", 4.0, -4.0),
+        ];
+        for (op, x, expected) in cases {
+            let genome = genome_from_ops(vec![("LOAD", Some(0.0)), (op, None)]);
+            match run_genome(&genome, x, cfg()) {
+                VmOutcome::Ok { output, .. } => assert_eq!(output, expected, "{op}"),
+                VmOutcome::Invalid { reason } => panic!("unexpected invalid for {op}: {reason}"),
+            }
+        }
+    }
+
+    #[test]
+    fn unary_domain_errors_invalid() {
+        let inf = f64::INFINITY;
+        for (op, x) in [
+            ("LOG", -1.0),
+            ("LOG", 0.0),
+            ("SQRT", -4.0),
+            ("EXP", 1000.0),
+            ("SIN", inf),
+            ("COS", -inf),
+            ("ABS", f64::NAN),
+            ("NEG", inf),
+        ] {
+            let genome = genome_from_ops(vec![("LOAD", Some(0.0)), (op, None)]);
+            match run_genome(&genome, x, cfg()) {
+                VmOutcome::Ok { output, .. } => panic!("expected invalid for {op}, got {output}"),
+                VmOutcome::Invalid { .. } => {}
+            }
+        }
+    }
+
+    #[test]
+    fn jz_computes_abs() {
+        // if x < 0 { -x } else { x }
+        let genome = genome_from_ops(vec![
+            ("LOAD", Some(0.0)),
+            ("PUSH", Some(0.0)),
+            ("LT", None),
+            ("JZ", Some(5.0)),
+            ("PUSH", Some(0.0)),
+            ("LOAD", Some(0.0)),
+            ("SUB", None),
+            ("HALT", None),
+            ("LOAD", Some(0.0)),
+        ]);
+        for (x, expected) in [(-3.0, 3.0), (2.5, 2.5), (0.0, 0.0)] {
+            match run_genome(&genome, x, cfg()) {
+                VmOutcome::Ok { output, .. } => assert_eq!(output, expected),
+                VmOutcome::Invalid { reason } => panic!("unexpected invalid: {reason}"),
+            }
+        }
+    }
+
+    #[test]
+    fn jnz_skips_when_nonzero() {
+        let genome = genome_from_ops(vec![
+            ("PUSH", Some(1.0)),
+            ("JNZ", Some(2.0)),
+            ("PUSH", Some(7.0)),
+            ("PUSH", Some(9.0)),
+        ]);
+        match run_genome(&genome, 0.0, cfg()) {
+            VmOutcome::Ok { output, .. } => assert_eq!(output, 9.0),
+            VmOutcome::Invalid { reason } => panic!("unexpected invalid: {reason}"),
+        }
+    }
+
+    #[test]
+    fn backward_jmp_hits_step_limit() {
+        let genome = genome_from_ops(vec![("NOP", None), ("JMP", Some(-1.0))]);
+        match run_genome(&genome, 0.0, cfg()) {
+            VmOutcome::Ok { output, .. } => panic!("expected invalid, got {output}"),
+            VmOutcome::Invalid { reason } => assert_eq!(reason, "max steps exceeded"),
+        }
+    }
+
+    #[test]
+    fn jump_before_start_invalid() {
+        let genome = genome_from_ops(vec![("JMP", Some(-3.0))]);
+        match run_genome(&genome, 0.0, cfg()) {
+            VmOutcome::Ok { output, .. } => panic!("expected invalid, got {output}"),
+            VmOutcome::Invalid { reason } => assert_eq!(reason, "jump out of range"),
+        }
+    }
+
+    #[test]
+    fn counts_executed_steps() {
+        let genome = genome_from_ops(vec![
+            ("PUSH", Some(1.0)),
+            ("JMP", Some(2.0)),
+            ("NOP", None),
+            ("HALT", None),
+            ("NOP", None),
+        ]);
+        match run_genome(&genome, 0.0, cfg()) {
+            VmOutcome::Ok { steps, .. } => assert_eq!(steps, 3),
+            VmOutcome::Invalid { reason } => panic!("unexpected invalid: {reason}"),
+        }
+    }
+
     #[test]
     fn step_limit_triggers_invalid() {
         let genome = genome_from_ops(vec![("NOP", None), ("NOP", None), ("NOP", None)]);
         let cfg = VmConfig { max_steps: 2 };
         match run_genome(&genome, 0.0, cfg) {
-            VmOutcome::Ok { output } => panic!("expected invalid, got {output}"),
+            VmOutcome::Ok { output, .. } => panic!("expected invalid, got {output}"),
             VmOutcome::Invalid { .. } => {}
         }
     }
+
+    #[test]
+    fn config_overrides_and_outcomes_use_the_api_shape() {
+        let cfg: VmConfig = serde_json::from_str("{}").unwrap();
+        assert_eq!(cfg.max_steps, VmConfig::default().max_steps);
+        let genome = genome_from_ops(vec![("LOAD", Some(0.0)), ("POP", None), ("POP", None)]);
+        let ok = serde_json::to_value(run_genome(&genome_from_ops(vec![]), 2.5, cfg)).unwrap();
+        assert_eq!(ok, serde_json::json!({"status": "ok", "output": 2.5, "steps": 0}));
+        let invalid = serde_json::to_value(run_genome(&genome, 1.0, cfg)).unwrap();
+        assert_eq!(
+            invalid,
+            serde_json::json!({"status": "invalid", "reason": "stack underflow"})
+        );
+    }
+
+    #[test]
+    fn trace_records_steps_and_failing_instruction() {
+        let genome = genome_from_ops(vec![
+            ("LOAD", Some(0.0)),
+            ("STORE", Some(1.0)),
+            ("ADD", None),
+        ]);
+        let (outcome, trace) = trace_genome(&genome, 3.0, cfg());
+        assert!(matches!(outcome, VmOutcome::Invalid { reason } if reason == "stack underflow"));
+        assert_eq!(trace.failed_at, Some(2));
+        let pcs: Vec<usize> = trace.steps.iter().map(|s| s.pc).collect();
+        assert_eq!(pcs, vec![0, 1, 2]);
+        assert_eq!(trace.steps[1].stack, vec![3.0]);
+        assert_eq!(trace.steps[2].registers, [3.0, 3.0, 0.0, 0.0]);
+
+        let looping = genome_from_ops(vec![("NOP", None), ("JMP", Some(-1.0))]);
+        let (_, trace) = trace_genome(&looping, 0.0, VmConfig { max_steps: 5 });
+        assert_eq!(trace.steps.len(), 5);
+        assert_eq!(trace.failed_at, Some(1));
+    }
+
+    /// The string-dispatch interpreter the VM used before genomes were decoded, kept to
+    /// check the decoded VM against and to benchmark it.
+    fn reference_run(genome: &Genome, x: f64, cfg: VmConfig) -> VmOutcome {
+        let mut registers = [0.0_f64; REGISTER_COUNT];
+        registers[0] = x;
+        let mut stack = Vec::new();
+        let mut pc = 0;
+        let mut steps = 0;
+        while pc < genome.instructions.len() {
+            if steps >= cfg.max_steps {
+                return VmOutcome::Invalid {
+                    reason: "max steps exceeded".to_string(),
+                };
+            }
+            steps += 1;
+            match reference_step(&genome.instructions[pc], pc, &mut stack, &mut registers) {
+                Ok(Some(next)) => pc = next,
+                Ok(None) => break,
+                Err(reason) => {
+                    return VmOutcome::Invalid {
+                        reason: reason.to_string(),
+                    }
+                }
+            }
+        }
+        let output = stack.last().copied().unwrap_or(registers[0]);
+        if !output.is_finite() {
+            return VmOutcome::Invalid {
+                reason: "non-finite output".to_string(),
+            };
+        }
+        VmOutcome::Ok { output, steps }
+    }
+
+    fn reference_step(
+        instr: &Instruction,
+        pc: usize,
+        stack: &mut Vec<f64>,
+        registers: &mut [f64; REGISTER_COUNT],
+    ) -> Result<Option<usize>, &'static str> {
+        let op = instr.op.as_str();
+        let register = || parse_register_index(instr.arg).ok_or("invalid register index");
+        match op {
+            "PUSH" => stack.push(instr.arg.ok_or("PUSH missing arg")?),
+            "LOAD" => stack.push(registers[register()?]),
+            "STORE" => {
+                let idx = register()?;
+                registers[idx] = pop(stack)?;
+            }
+            "ADD" | "SUB" | "MUL" | "DIV" | "LT" | "GT" | "EQ" => {
+                let (a, b) = pop_two(stack)?;
+                stack.push(match op {
+                    "ADD" => a + b,
+                    "SUB" => a - b,
+                    "MUL" => a * b,
+                    "DIV" if b.abs() < EPS_DIVISOR => return Err("division by near-zero"),
+                    "DIV" => a / b,
+                    "LT" => f64::from(u8::from(a < b)),
+                    "GT" => f64::from(u8::from(a > b)),
+                    _ => f64::from(u8::from(a == b)),
+                });
+            }
+            "SIN" | "COS" | "EXP" | "LOG" | "SQRT" | "ABS" | "NEG" => {
+                let a = pop(stack)?;
+                if !a.is_finite() {
+                    return Err("non-finite operand");
+                }
+                stack.push(match op {
+                    "SIN" => a.sin(),
+                    "COS" => a.cos(),
+                    "EXP" if !a.exp().is_finite() => return Err("exp overflow"),
+                    "EXP" => a.exp(),
+                    "LOG" if a <= 0.0 => return Err("log of non-positive"),
+                    "LOG" => a.ln(),
+                    "SQRT" if a < 0.0 => return Err("sqrt of negative"),
+                    "SQRT" => a.sqrt(),
+                    "ABS" => a.abs(),
+                    _ => -a,
+                });
+            }
+            "DUP" => {
+                let top = *stack.last().ok_or(UNDERFLOW)?;
+                stack.push(top);
+            }
+            "SWAP" => {
+                if stack.len() < 2 {
+                    return Err(UNDERFLOW);
+                }
+                let len = stack.len();
+                stack.swap(len - 1, len - 2);
+            }
+            "POP" => {
+                pop(stack)?;
+            }
+            "JMP" | "JZ" | "JNZ" => {
+                let offset = parse_jump_offset(instr.arg).ok_or("invalid jump offset")?;
+                let taken = op == "JMP" || (pop(stack)? == 0.0) == (op == "JZ");
+                if taken {
+                    return pc.checked_add_signed(offset).map(Some).ok_or("jump out of range");
+                }
+            }
+            "HALT" => return Ok(None),
+            "NOP" => {}
+            _ => return Err("unknown opcode"),
+        }
+        Ok(Some(pc + 1))
+    }
+
+    /// Random genomes with some ops and args corrupted, so every fault path is exercised.
+    fn messy_population(size: usize) -> Vec<Genome> {
+        let mut rng = seeded_rng(21);
+        let args = [None, Some(0.0), Some(1.5), Some(3.0), Some(4.0), Some(-2.0), Some(9.0)];
+        (0..size)
+            .map(|i| {
+                let mut genome = random_genome(&mut rng, &OpSet::all());
+                for (j, instr) in genome.instructions.iter_mut().enumerate() {
+                    match (i + j) % 11 {
+                        0 => instr.arg = args[(i * 7 + j) % args.len()],
+                        1 if j % 3 == 0 => instr.op = "FROB".to_string(),
+                        _ => {}
+                    }
+                }
+                genome
+            })
+            .collect()
+    }
+
+    const INPUTS: [f64; 8] = [-3.0, -1.0, -0.5, 0.0, 0.5, 1.0, 2.0, 7.0];
+
+    #[test]
+    fn decoded_vm_matches_reference_interpreter() {
+        for genome in messy_population(400) {
+            let mut program = Program::lazy(&genome);
+            let mut vm = Vm::default();
+            for x in INPUTS {
+                assert_eq!(
+                    format!("{:?}", vm.run(&mut program, x, cfg())),
+                    format!("{:?}", reference_run(&genome, x, cfg())),
+                    "genome {genome:?} on x={x}"
+                );
+            }
+        }
+    }
+
+    /// Population of a native run after some generations, where most genomes are valid.
+    fn evolved_population() -> Vec<Genome> {
+        let cfg = serde_json::from_value(serde_json::json!({
+            "seed": 3,
+            "population": 1000,
+            "generations": 40,
+            "mutation_rate": 0.5,
+            "task": "poly2",
+            "evaluator": "native"
+        }))
+        .unwrap();
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let runs = new_store();
+            let evaluators = Evaluators::new("http://127.0.0.1:0", 0).unwrap();
+            let run_id = create_run(cfg, &runs, &evaluators).await.unwrap();
+            advance_run(&runs, &run_id, 40, &evaluators).await.unwrap();
+            get_snapshot(&runs, &run_id).await.unwrap().population
+        })
+    }
+
+    /// Scores populations the way `fitness::score_genome` does, stopping each genome at its
+    /// first invalid case, against the string-dispatch interpreter.
+    /// `cargo test --release -- --ignored --nocapture vm_speedup` prints the timings.
+    #[test]
+    #[ignore]
+    fn vm_speedup_across_population() {
+        let cases: Vec<f64> = get_cases("poly2").into_iter().map(|(x, _)| x).collect();
+        let mut rng = seeded_rng(3);
+        let random = (0..1000).map(|_| random_genome(&mut rng, &OpSet::all())).collect();
+        for (label, population) in [("random", random), ("evolved", evolved_population())] {
+            let time = |score: &dyn Fn(&Genome) -> usize| {
+                let start = Instant::now();
+                let mut valid_runs = 0;
+                for _ in 0..20 {
+                    valid_runs = population.iter().map(score).sum::<usize>();
+                }
+                (start.elapsed() / 20, valid_runs)
+            };
+            let valid = |outcome: VmOutcome| matches!(outcome, VmOutcome::Ok { .. });
+            let (reference, reference_runs) = time(&|genome| {
+                cases
+                    .iter()
+                    .take_while(|&&x| valid(reference_run(genome, x, cfg())))
+                    .count()
+            });
+            let (decoded, decoded_runs) = time(&|genome| {
+                let mut program = Program::lazy(genome);
+                let mut vm = Vm::default();
+                cases
+                    .iter()
+                    .take_while(|&&x| valid(vm.run(&mut program, x, cfg())))
+                    .count()
+            });
+            assert_eq!(reference_runs, decoded_runs);
+            println!(
+                "{label} population of {}: string dispatch {reference:?}, decoded {decoded:?}, \
+                 speedup {:.2}x",
+                population.len(),
+                reference.as_secs_f64() / decoded.as_secs_f64()
+            );
+        }
+    }
 }
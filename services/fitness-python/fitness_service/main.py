@@ -13,5 +13,5 @@ async def health() -> dict:
 
 @app.post("/score", response_model=ScoreResponse)
 async def score(request: ScoreRequest) -> ScoreResponse:
-    fitness = score_genomes(request.task, request.genomes)
-    return ScoreResponse(fitness=fitness)
+    fitness, errors, steps = score_genomes(request.task, request.genomes)
+    return ScoreResponse(fitness=fitness, errors=errors, steps=steps)
@@ -19,3 +19,7 @@ class ScoreRequest(BaseModel):
 
 class ScoreResponse(BaseModel):
     fitness: List[float]
+    # Per-genome absolute error on each test case; null marks an invalid genome.
+    errors: List[List[Optional[float]]]
+    # Mean executed VM steps per test case; invalid genomes report the step limit.
+    steps: List[float]
@@ -16,6 +16,35 @@ def _parse_register(arg: float | None) -> int | None:
     return None
 
 
+def _parse_jump(arg: float | None) -> int | None:
+    if arg is None or not math.isfinite(arg) or arg != int(arg) or abs(arg) > 1e9:
+        return None
+    return int(arg)
+
+
+def _unary(op: str, a: float) -> float | None:
+    """Applies a unary op; None marks the genome invalid, as in the engine VM."""
+    if not math.isfinite(a):
+        return None
+    if op == "SIN":
+        return math.sin(a)
+    if op == "COS":
+        return math.cos(a)
+    if op == "EXP":
+        try:
+            value = math.exp(a)
+        except OverflowError:
+            return None
+        return value if math.isfinite(value) else None
+    if op == "LOG":
+        return math.log(a) if a > 0.0 else None
+    if op == "SQRT":
+        return math.sqrt(a) if a >= 0.0 else None
+    if op == "ABS":
+        return abs(a)
+    return -a
+
+
 def _pop_two(stack: list[float]) -> tuple[float, float] | None:
     if len(stack) < 2:
         return None
@@ -24,7 +53,10 @@ def _pop_two(stack: list[float]) -> tuple[float, float] | None:
     return a, b
 
 
-def run_genome(genome: Genome, x: float, max_steps: int = DEFAULT_MAX_STEPS) -> tuple[bool, float | None]:
+def run_genome(
+    genome: Genome, x: float, max_steps: int = DEFAULT_MAX_STEPS
+) -> tuple[bool, float | None, int]:
+    """Returns (ok, output, executed steps)."""
     registers: List[float] = [0.0, 0.0, 0.0, 0.0]
     registers[0] = x
     stack: List[float] = []
@@ -34,7 +66,7 @@ def run_genome(genome: Genome, x: float, max_steps: int = DEFAULT_MAX_STEPS) ->
 
     while pc < len(instructions):
         if steps >= max_steps:
-            return False, None
+            return False, None, steps
 
         instr = instructions[pc]
         steps += 1
@@ -42,87 +74,132 @@ def run_genome(genome: Genome, x: float, max_steps: int = DEFAULT_MAX_STEPS) ->
 
         if op == "PUSH":
             if instr.arg is None:
-                return False, None
+                return False, None, steps
             stack.append(instr.arg)
         elif op == "LOAD":
             idx = _parse_register(instr.arg)
             if idx is None:
-                return False, None
+                return False, None, steps
             stack.append(registers[idx])
         elif op == "STORE":
             idx = _parse_register(instr.arg)
             if idx is None or not stack:
-                return False, None
+                return False, None, steps
             registers[idx] = stack.pop()
         elif op == "ADD":
             values = _pop_two(stack)
             if values is None:
-                return False, None
+                return False, None, steps
             a, b = values
             stack.append(a + b)
         elif op == "SUB":
             values = _pop_two(stack)
             if values is None:
-                return False, None
+                return False, None, steps
             a, b = values
             stack.append(a - b)
         elif op == "MUL":
             values = _pop_two(stack)
             if values is None:
-                return False, None
+                return False, None, steps
             a, b = values
             stack.append(a * b)
         elif op == "DIV":
             values = _pop_two(stack)
             if values is None:
-                return False, None
+                return False, None, steps
             a, b = values
             if abs(b) < EPS_DIVISOR:
-                return False, None
+                return False, None, steps
             stack.append(a / b)
+        elif op in ("SIN", "COS", "EXP", "LOG", "SQRT", "ABS", "NEG"):
+            if not stack:
+                return False, None, steps
+            value = _unary(op, stack.pop())
+            if value is None:
+                return False, None, steps
+            stack.append(value)
         elif op == "DUP":
             if not stack:
-                return False, None
+                return False, None, steps
             stack.append(stack[-1])
         elif op == "SWAP":
             if len(stack) < 2:
-                return False, None
+                return False, None, steps
             stack[-1], stack[-2] = stack[-2], stack[-1]
         elif op == "POP":
             if not stack:
-                return False, None
+                return False, None, steps
             stack.pop()
+        elif op in ("LT", "GT", "EQ"):
+            values = _pop_two(stack)
+            if values is None:
+                return False, None, steps
+            a, b = values
+            if op == "LT":
+                holds = a < b
+            elif op == "GT":
+                holds = a > b
+            else:
+                holds = a == b
+            stack.append(1.0 if holds else 0.0)
+        elif op in ("JMP", "JZ", "JNZ"):
+            offset = _parse_jump(instr.arg)
+            if offset is None:
+                return False, None, steps
+            taken = True
+            if op != "JMP":
+                if not stack:
+                    return False, None, steps
+                taken = (stack.pop() == 0.0) == (op == "JZ")
+            if taken:
+                # Targets past the end simply end the program.
+                target = pc + offset
+                if target < 0:
+                    return False, None, steps
+                pc = target
+                continue
         elif op == "HALT":
             break
         elif op == "NOP":
             pass
         else:
-            return False, None
+            return False, None, steps
 
         pc += 1
 
     output = stack[-1] if stack else registers[0]
     if not math.isfinite(output):
-        return False, None
+        return False, None, steps
 
-    return True, output
+    return True, output, steps
 
 
-def score_genomes(task: str, genomes: List[Genome]) -> List[float]:
+def score_genomes(
+    task: str, genomes: List[Genome]
+) -> tuple[List[float], List[List[float | None]], List[float]]:
     cases: List[Tuple[float, float]] = get_cases(task)
     fitnesses: List[float] = []
+    case_errors: List[List[float | None]] = []
+    mean_steps: List[float] = []
     for genome in genomes:
         invalid = False
         errors: List[float] = []
+        total_steps = 0
         for x, expected in cases:
-            ok, output = run_genome(genome, x)
+            ok, output, steps = run_genome(genome, x)
             if not ok or output is None:
                 invalid = True
                 break
             errors.append(abs(output - expected))
+            total_steps += steps
         if invalid:
             fitnesses.append(1e-9)
+            case_errors.append([None] * len(cases))
+            mean_steps.append(float(DEFAULT_MAX_STEPS))
         else:
             mean_error = sum(errors) / len(errors) if errors else float("inf")
             fitnesses.append(1.0 / (1.0 + mean_error))
-    return fitnesses
+            case_errors.append(errors)
+            mean_steps.append(total_steps / len(cases) if cases else 0.0)
+    return fitnesses, case_errors, mean_steps
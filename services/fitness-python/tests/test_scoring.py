@@ -0,0 +1,37 @@
+import math
+import unittest
+
+from fitness_service.models import Genome, Instruction
+from fitness_service.scoring import run_genome, score_genomes
+
+
+def genome(*ops: tuple[str, float | None]) -> Genome:
+    return Genome(instructions=[Instruction(op=op, arg=arg) for op, arg in ops])
+
+
+class UnaryOpTests(unittest.TestCase):
+    def test_non_finite_operands_are_invalid(self) -> None:
+        for op in ("SIN", "COS", "EXP", "LOG", "SQRT", "ABS", "NEG"):
+            for x in (math.inf, -math.inf, math.nan):
+                ok, output, _ = run_genome(genome(("LOAD", 0.0), (op, None)), x)
+                self.assertFalse(ok, f"{op}({x})")
+                self.assertIsNone(output)
+
+    def test_exp_overflow_is_invalid(self) -> None:
+        ok, _, _ = run_genome(genome(("LOAD", 0.0), ("EXP", None)), 1000.0)
+        self.assertFalse(ok)
+
+    def test_overflowing_mul_into_sin_scores_as_invalid(self) -> None:
+        # 1e200 * 1e200 overflows to inf before SIN sees it.
+        overflow = genome(("PUSH", 1e200), ("DUP", None), ("MUL", None), ("SIN", None))
+        fitness, errors, _ = score_genomes("poly2", [overflow])
+        self.assertEqual(fitness, [1e-9])
+        self.assertTrue(all(e is None for e in errors[0]))
+
+    def test_finite_operands_still_work(self) -> None:
+        ok, output, steps = run_genome(genome(("LOAD", 0.0), ("COS", None)), 0.0)
+        self.assertEqual((ok, output, steps), (True, 1.0, 2))
+
+
+if __name__ == "__main__":
+    unittest.main()